@@ -0,0 +1,39 @@
+use gdnative::prelude::*;
+use gdnative::api::{CollisionShape, ConcavePolygonShape, MeshInstance, StaticBody};
+
+use crate::common::*;
+
+
+/// a StaticBody with a concave shape, added as a child of the chunk's node when the chunk first has solid voxels
+/// only touched on the main thread, the faces are built by the workers
+pub struct ChunkCollision {
+	shape: Option<Ref<ConcavePolygonShape>>,
+}
+
+
+impl ChunkCollision {
+	pub fn new() -> Self {
+		Self {
+			shape: None,
+		}
+	}
+
+	/// replace the collision faces, see voxel_core::collision::collision_faces
+	pub fn set_faces(&mut self, faces: &[Vec3], node: &Ref<MeshInstance>) {
+		if self.shape.is_none() && faces.is_empty() {
+			return;
+		}
+		let shape = self.shape.get_or_insert_with(|| {
+			let shape = ConcavePolygonShape::new().into_shared();
+			let collision_shape = CollisionShape::new();
+			collision_shape.set_shape(shape.clone());
+			let body = StaticBody::new();
+			body.set_name("Collision");
+			body.add_child(collision_shape.into_shared(), false);
+			unsafe { node.assume_safe() }.add_child(body.into_shared(), false);
+			shape
+		});
+		let faces: Vector3Array = faces.iter().map(|v| v.to_godot()).collect();
+		unsafe { shape.assume_safe() }.set_faces(faces);
+	}
+}
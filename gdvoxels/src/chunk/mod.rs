@@ -1,48 +1,98 @@
 use std::time::Instant;
 use gdnative::api::{ArrayMesh, MeshInstance};
 use gdnative::prelude::*;
+use voxel_core::chunk::{Border, ChunkCore, Neighbours};
+use voxel_core::collision::collision_faces;
 
+mod collision;
 mod mesh;
-pub mod core;
 
 use crate::common::*;
 use crate::materials::MaterialList;
+use self::collision::*;
 use self::mesh::*;
-use self::core::*;
 
 
 pub struct Chunk {
-	pub wpos: Vector3,
+	pub wpos: Vec3,
 	pub loc: ChunkLoc,
+	/// 0 for full detail chunks, otherwise each voxel covers 2^lod voxels on each axis and loc is in units of 2^lod chunks
+	pub lod: u8,
 	pub node: Ref<MeshInstance>,
 	core: ChunkCore,
 	mesh: ChunkMesh,
+	collision: ChunkCollision,
+	/// collision faces built by a worker, applied with update_collision once the chunk is on the main thread
+	collision_faces: Option<Vec<Vec3>>,
 	since_change: Instant,
+	/// incremented on every change to the voxels or borders, to detect outdated snapshots
+	version: u32,
+}
+
+/// a copy of a chunk's voxels, to be optimised by a worker thread
+pub struct ChunkSnapshot {
+	pub loc: ChunkLoc,
+	version: u32,
+	core: ChunkCore,
+}
+
+/// the result of optimising a snapshot, to be swapped into the chunk with Chunk::apply_optimised
+pub struct OptimisedChunk {
+	pub loc: ChunkLoc,
+	version: u32,
+	core: ChunkCore,
+	mesh: OptimisedMesh,
 }
 
 
 impl Chunk {
-	pub fn new(wpos: Vector3, core: ChunkCore) -> Self {
+	pub fn new(wpos: Vec3, core: ChunkCore) -> Self {
 		let wpos = (wpos / WIDTH_F).floor() * WIDTH_F;
 		let node = unsafe { MeshInstance::new().assume_shared() };
 		Self {
 			wpos,
 			loc: wpos_to_loc(wpos),
+			lod: 0,
 			node,
 			core,
 			mesh: ChunkMesh::new(),
+			collision: ChunkCollision::new(),
+			collision_faces: None,
 			since_change: Instant::now(),
+			version: 0,
 		}
 	}
 
+	/// a lod region at loc, in units of 2^lod chunks
+	pub fn new_lod(loc: ChunkLoc, lod: u8, core: ChunkCore) -> Self {
+		let wpos = loc_to_wpos(loc) * (1 << lod) as f32;
+		let mut chunk = Self::new(wpos, core);
+		chunk.loc = loc;
+		chunk.lod = lod;
+		chunk
+	}
+
+	/// size of a voxel in this chunk
+	pub fn scale(&self) -> f32 {
+		(1 << self.lod) as f32
+	}
+
 	pub fn since_change(&self) -> Instant {
 		self.since_change
 	}
 
+	pub fn core(&self) -> &ChunkCore {
+		&self.core
+	}
+
 	pub fn is_empty(&self) -> bool {
 		self.core.empty
 	}
 
+	pub fn is_modified(&self) -> bool {
+		self.core.modified
+	}
+
 	pub fn mark_empty(&mut self, state: bool) {
 		self.core.empty = state;
 	}
@@ -68,14 +118,55 @@ impl Chunk {
 			return;
 		}
 		let start = Instant::now();
-		self.mesh.optimise(&self.core, materials);
+		self.core.compact();
+		if self.lod > 0 {
+			self.mesh.optimise_cubes(&self.core, materials);
+		}
+		else {
+			self.mesh.optimise(&self.core, materials);
+		}
 		if DEBUG_MESH_TIMES {
 			let t = start.elapsed().as_micros() as f64 / 1000.0;
 			godot_print!("optimised mesh took {}ms", t);
 		}
 	}
 
-	pub fn remesh_pos(&mut self, materials: &MaterialList, pos: Vector3, old_voxel: Voxel) {
+	/// build the collision faces ahead of time, so update_collision only has to hand them to Godot
+	pub fn build_collision(&mut self) {
+		self.collision_faces = Some(collision_faces(&self.core));
+	}
+
+	/// update the collision shape to match the voxels, must be called on the main thread
+	pub fn update_collision(&mut self) {
+		let start = Instant::now();
+		let faces = self.collision_faces.take().unwrap_or_else(|| collision_faces(&self.core));
+		self.collision.set_faces(&faces, &self.node);
+		if DEBUG_MESH_TIMES {
+			let t = start.elapsed().as_micros() as f64 / 1000.0;
+			godot_print!("collision took {}ms", t);
+		}
+	}
+
+	pub fn snapshot(&self) -> ChunkSnapshot {
+		ChunkSnapshot {
+			loc: self.loc,
+			version: self.version,
+			core: self.core.clone(),
+		}
+	}
+
+	/// swap in the optimised voxels and mesh, unless the chunk changed after the snapshot was taken
+	/// returns false if the result was outdated
+	pub fn apply_optimised(&mut self, optimised: OptimisedChunk, materials: &MaterialList) -> bool {
+		if optimised.version != self.version {
+			return false;
+		}
+		self.core = optimised.core;
+		self.mesh.apply_optimised(optimised.mesh, materials);
+		true
+	}
+
+	pub fn remesh_pos(&mut self, materials: &MaterialList, pos: Vec3, old_voxel: Voxel) {
 		let start = Instant::now();
 		self.mesh.remesh_partial(&self.core, materials, pos, old_voxel);
 		if DEBUG_MESH_TIMES {
@@ -84,14 +175,86 @@ impl Chunk {
 		}
 	}
 
+	pub fn border(&self, face: usize) -> Border {
+		self.core.border(face)
+	}
+
+	/// returns true if the neighbour changed and the chunk needs to be remeshed
+	pub fn set_neighbour(&mut self, face: usize, border: Border) -> bool {
+		let changed = self.core.set_neighbour(face, border);
+		if changed {
+			self.version += 1;
+		}
+		changed
+	}
+
+	/// returns true if any neighbour changed and the chunk needs to be remeshed
+	pub fn set_neighbours(&mut self, neighbours: Neighbours) -> bool {
+		let changed = self.core.set_neighbours(neighbours);
+		if changed {
+			self.version += 1;
+		}
+		changed
+	}
+
+	pub fn diagonal_opacity(&self, offset: ChunkLoc) -> u32 {
+		self.core.diagonal_opacity(offset)
+	}
+
+	/// returns true if the edge or corner changed and the chunk needs to be remeshed
+	pub fn set_diagonal(&mut self, diagonal: usize, mask: u32) -> bool {
+		let changed = self.core.set_diagonal(diagonal, mask);
+		if changed {
+			self.version += 1;
+		}
+		changed
+	}
+
+	/// update the mesh after an edge or corner of a diagonal neighbour changed
+	/// only smooth meshes change noticeably, cube meshes wait until they are optimised
+	pub fn remesh_diagonal(&mut self, materials: &MaterialList) {
+		self.since_change = Instant::now();
+		self.mesh.remesh_diagonal(&self.core, materials);
+	}
+
+	/// update one face of the voxel at pos after the voxel it touches in a neighbouring chunk changed
+	pub fn remesh_face(&mut self, materials: &MaterialList, pos: Vec3, face: usize) {
+		self.since_change = Instant::now();
+		self.mesh.remesh_face(&self.core, materials, pos, face);
+	}
+
 	#[inline]
-	pub fn get_voxel(&self, pos: Vector3) -> Voxel {
+	pub fn get_voxel(&self, pos: Vec3) -> Voxel {
 		self.core.get_voxel(pos)
 	}
 	
 	#[inline]
-	pub fn set_voxel(&mut self, pos: Vector3, voxel: Voxel) {
+	pub fn set_voxel(&mut self, pos: Vec3, voxel: Voxel) {
 		self.since_change = Instant::now();
+		self.version += 1;
+		self.collision_faces = None;
+		if self.core.get_voxel(pos) != voxel {
+			self.core.modified = true;
+		}
 		self.core.set_voxel(pos, voxel);
 	}
 }
+
+
+impl ChunkSnapshot {
+	pub fn optimise(mut self, materials: &MaterialList) -> OptimisedChunk {
+		let start = Instant::now();
+		self.core.compact();
+		let mesh = ChunkMesh::build_optimised(&self.core, materials);
+		if DEBUG_MESH_TIMES {
+			let t = start.elapsed().as_micros() as f64 / 1000.0;
+			godot_print!("optimised mesh took {}ms", t);
+		}
+		OptimisedChunk {
+			loc: self.loc,
+			version: self.version,
+			core: self.core,
+			mesh,
+		}
+	}
+}
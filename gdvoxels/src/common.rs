@@ -1,119 +1,74 @@
 use gdnative::prelude::*;
-use gdnative::core_types::Axis;
 
-pub const WIDTH: usize = 32;
-pub const AREA: usize = WIDTH * WIDTH;
-pub const VOLUME: usize = AREA * WIDTH;
+pub use voxel_core::common::*;
+pub use voxel_core::math::{Vec2, Vec3};
+use voxel_core::math;
+use voxel_core::movement::SlideMotion;
+use voxel_core::raycast::{RayFilter, RayHit};
 
-pub const WIDTH_F: f32 = WIDTH as f32;
-pub const WIDTH_I8: i8 = WIDTH as i8;
-
-pub type Voxel = u8;
-pub const EMPTY: Voxel = 0;
-
-pub const DEBUG_UVS: bool = true;
 pub const DEBUG_MESH_TIMES: bool = false;
 
-/// Represents a chunk location
-/// Loc(1,2,3) correspsonds to the chunk at (32, 64, 96) assuming a chunk size of 32
-pub type ChunkLoc = (i32, i32, i32);
-pub type VoxelPos = (i8, i8, i8);
-
-/// Convert world coordinate to a chunk location
-#[inline]
-pub fn wpos_to_locv(world_pos: Vector3) -> Vector3 {
-	(world_pos / WIDTH_F).floor()
-}
-
-#[inline]
-pub fn locv_to_wpos(locv: Vector3) -> Vector3 {
-	locv * WIDTH_F
-}
-
-#[inline]
-pub fn wpos_to_loc(world_pos: Vector3) -> ChunkLoc {
-	locv_to_loc(wpos_to_locv(world_pos))
-}
-
-/// Convert Vector3 to i32 tuple
-#[inline]
-pub fn locv_to_loc(loc: Vector3) -> ChunkLoc {
-	(loc.x as i32, loc.y as i32, loc.z as i32)
-}
-
-#[inline]
-#[allow(unused)]
-pub fn loc_to_wpos(loc: ChunkLoc) -> Vector3 {
-	Vector3::new(loc.0 as f32, loc.1 as f32, loc.2 as f32) * WIDTH_F
-}
-
-#[inline]
-pub fn loc_to_locv(loc: ChunkLoc) -> Vector3 {
-	Vector3::new(loc.0 as f32, loc.1 as f32, loc.2 as f32)
+/// convert Godot's vectors to the ones voxel_core uses
+pub trait ToCore {
+	type Core;
+	fn to_core(self) -> Self::Core;
 }
 
-/// convert world coordinate to a position within the chunk
-#[inline]
-pub fn wpos_to_vposv(world_pos: Vector3) -> Vector3 {
-	world_pos.floor().posmod(WIDTH_F)
+/// convert voxel_core's vectors to Godot's
+pub trait ToGodot {
+	type Godot;
+	fn to_godot(self) -> Self::Godot;
 }
 
-/// [i32] to [Vector3]
-#[inline]
-pub const fn ivec3(x: i32, y: i32, z: i32) -> Vector3 {
-	Vector3::new(x as f32, y as f32, z as f32)
-}
-
-#[inline]
-pub const fn uvec3(x: usize, y: usize, z: usize) -> Vector3 {
-	Vector3::new(x as f32, y as f32, z as f32)
-}
-
-#[inline]
-pub fn vposv_in_bounds(pos: Vector3) -> bool{
-	pos.x >= 0.0 && pos.x < WIDTH_F &&
-	pos.y >= 0.0 && pos.y < WIDTH_F &&
-	pos.z >= 0.0 && pos.z < WIDTH_F
+impl ToCore for Vector3 {
+	type Core = Vec3;
+	#[inline]
+	fn to_core(self) -> Vec3 {
+		Vec3::new(self.x, self.y, self.z)
+	}
 }
 
-#[inline]
-pub fn vpos_in_bounds(vpos: VoxelPos) -> bool{
-	vpos.0 >= 0 && vpos.0 < WIDTH_I8 &&
-	vpos.1 >= 0 && vpos.1 < WIDTH_I8 &&
-	vpos.2 >= 0 && vpos.2 < WIDTH_I8
+impl ToCore for Vector2 {
+	type Core = Vec2;
+	#[inline]
+	fn to_core(self) -> Vec2 {
+		Vec2::new(self.x, self.y)
+	}
 }
 
-#[inline]
-pub fn vpos_to_index(vpos: VoxelPos) -> usize {
-	vpos.0 as usize * AREA
-	+ vpos.1 as usize * WIDTH
-	+ vpos.2 as usize
+impl ToCore for Aabb {
+	type Core = math::Aabb;
+	#[inline]
+	fn to_core(self) -> math::Aabb {
+		math::Aabb::new(self.position.to_core(), self.size.to_core())
+	}
 }
 
-#[inline]
-pub fn vposv_to_index(vposv: Vector3) -> usize {
-	vposv.x as usize * AREA
-	+ vposv.y as usize * WIDTH
-	+ vposv.z as usize
+impl ToGodot for Vec3 {
+	type Godot = Vector3;
+	#[inline]
+	fn to_godot(self) -> Vector3 {
+		Vector3::new(self.x, self.y, self.z)
+	}
 }
 
-#[inline]
-#[allow(unused)]
-pub fn index_to_vpos(i: usize) -> VoxelPos {
-	(
-		(i/AREA) as i8,
-		(i/WIDTH % WIDTH) as i8,
-		(i % WIDTH) as i8
-	)
+impl ToGodot for math::Aabb {
+	type Godot = Aabb;
+	#[inline]
+	fn to_godot(self) -> Aabb {
+		Aabb {
+			position: self.pos.to_godot(),
+			size: self.size.to_godot(),
+		}
+	}
 }
 
-#[inline]
-pub fn index_to_vposv(i: usize) -> Vector3 {
-	Vector3::new(
-		((i / AREA) as f32).floor(),
-		((i/WIDTH % WIDTH) as f32).floor(),
-		(i % WIDTH) as f32
-	)
+impl ToGodot for Vec2 {
+	type Godot = Vector2;
+	#[inline]
+	fn to_godot(self) -> Vector2 {
+		Vector2::new(self.x, self.y)
+	}
 }
 
 #[derive(ToVariant)]
@@ -123,86 +78,87 @@ pub struct Ray {
 	normal: Vector3,
 	voxel: Voxel,
 	distance: f32,
+	voxel_pos: Vector3,
+	previous_pos: Vector3,
+	unloaded: bool,
 }
 
-impl Ray {
-	pub fn hit(pos: Vector3, normal: Vector3, voxel: Voxel, distance: f32) -> Self {
+impl From<RayHit> for Ray {
+	fn from(ray: RayHit) -> Self {
 		Self {
-			hit: true,
-			pos,
-			normal,
-			voxel,
-			distance,
+			hit: ray.hit,
+			pos: ray.pos.to_godot(),
+			normal: ray.normal.to_godot(),
+			voxel: ray.voxel,
+			distance: ray.distance,
+			voxel_pos: ray.voxel_pos.to_godot(),
+			previous_pos: ray.previous_pos.to_godot(),
+			unloaded: ray.unloaded,
 		}
 	}
+}
 
-	pub fn miss(pos: Vector3, distance: f32) -> Self {
+/// the results of cast_rays, one entry per ray in each array
+#[derive(ToVariant)]
+pub struct Rays {
+	hit: ByteArray,
+	pos: Vector3Array,
+	normal: Vector3Array,
+	voxel: Int32Array,
+	distance: Float32Array,
+	voxel_pos: Vector3Array,
+	previous_pos: Vector3Array,
+	unloaded: ByteArray,
+}
+
+impl From<Vec<RayHit>> for Rays {
+	fn from(rays: Vec<RayHit>) -> Self {
+		let vectors = |f: fn(&RayHit) -> Vec3| rays.iter().map(|r| f(r).to_godot()).collect();
 		Self {
-			hit: false,
-			pos,
-			normal: Vector3::ZERO,
-			voxel: EMPTY,
-			distance,
+			hit: rays.iter().map(|r| r.hit as u8).collect(),
+			pos: vectors(|r| r.pos),
+			normal: vectors(|r| r.normal),
+			voxel: rays.iter().map(|r| r.voxel as i32).collect(),
+			distance: rays.iter().map(|r| r.distance).collect(),
+			voxel_pos: vectors(|r| r.voxel_pos),
+			previous_pos: vectors(|r| r.previous_pos),
+			unloaded: rays.iter().map(|r| r.unloaded as u8).collect(),
 		}
 	}
 }
 
-pub trait VoxelData {
-	fn name(&self) -> String;
-	fn is_air(&self) -> bool;
-	fn is_surface(&self) -> bool;
-	fn is_transparent(&self) -> bool;
-	fn is_opaque(&self) -> bool;
-}
-
-impl VoxelData for Voxel {
-	fn name(&self) -> String {
-		match *self {
-			0 => "air".into(),
-			1 => "stone".into(),
-			2 => "dirt".into(),
-			3 => "grass".into(),
-			4 => "frame".into(),
-			5 => "leaves".into(),
-			255 => "debug".into(),
-			other => format!("{}", other),
-		}
-	}
-	
-	#[inline]
-	fn is_air(&self) -> bool {
-		*self == 0
-	}
-
-	#[inline]
-	fn is_surface(&self) -> bool {
-		*self != 0
-	}
-
-	#[inline]
-	fn is_transparent(&self) -> bool {
-		*self == 0
-		|| *self == 4 
-		|| *self == 5
-	}
-
-	#[inline]
-	fn is_opaque(&self) -> bool {
-		!self.is_transparent()
+/// build a ray filter from a dictionary like
+/// `{ "ignore_transparent": true, "ignore_unselectable": true, "hit_only": [1, 2], "ignore": [5], "stop_at_unloaded": true }`
+/// missing keys are false or empty, which hits every voxel and goes through unloaded chunks
+pub fn ray_filter(options: &Dictionary) -> RayFilter {
+	let flag = |key: &str| options.get(key).and_then(|v| v.to::<bool>()).unwrap_or(false);
+	let ids = |key: &str| options.get(key).and_then(|v| v.to::<Vec<Voxel>>()).unwrap_or_default();
+	RayFilter {
+		ignore_transparent: flag("ignore_transparent"),
+		ignore_unselectable: flag("ignore_unselectable"),
+		hit_only: ids("hit_only"),
+		ignore: ids("ignore"),
+		stop_at_unloaded: flag("stop_at_unloaded"),
 	}
 }
 
-
-pub trait AxisToVector3 {
-	fn vec(&self) -> Vector3;
+#[derive(ToVariant)]
+pub struct Motion {
+	aabb: Aabb,
+	motion: Vector3,
+	velocity: Vector3,
+	grounded: bool,
+	normals: Vec<Vector3>,
 }
 
-impl AxisToVector3 for Axis {
-	fn vec(&self) -> Vector3 {
-		match self {
-			Axis::X => Vector3::RIGHT,
-			Axis::Y => Vector3::UP,
-			Axis::Z => Vector3::BACK,
+impl From<SlideMotion> for Motion {
+	fn from(slide: SlideMotion) -> Self {
+		Self {
+			aabb: slide.aabb.to_godot(),
+			motion: slide.motion.to_godot(),
+			velocity: slide.velocity.to_godot(),
+			grounded: slide.grounded,
+			normals: slide.normals.into_iter().map(Vec3::to_godot).collect(),
 		}
 	}
 }
@@ -4,7 +4,9 @@ mod world;
 mod chunk;
 mod common;
 mod materials;
+mod registry;
 mod terrain;
+mod workers;
 
 use world::*;
 
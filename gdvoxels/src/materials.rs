@@ -1,16 +1,34 @@
 use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use gdnative::prelude::*;
-use gdnative::api::ShaderMaterial;
+use gdnative::api::{Image, Shader, ShaderMaterial, Texture, TextureArray, TextureLayered};
 
 use crate::common::*;
+use crate::registry::registry;
 
 
 const DEFAULT_PATH: &str = "res://addons/voxel-engine/materials/default.material";
+const ARRAY_SHADER_PATH: &str = "res://addons/voxel-engine/materials/voxel_array.shader";
+const ARRAY_ALPHA_SHADER_PATH: &str = "res://addons/voxel-engine/materials/voxel_array_alpha.shader";
+pub const TEXTURE_DIR: &str = "res://addons/voxel-engine/textures";
+/// size used for the texture array if no textures could be loaded
+const DEFAULT_TEXTURE_SIZE: i64 = 16;
 
 pub struct MaterialList {
 	mats: HashMap<Voxel, Ref<ShaderMaterial, Shared>>,
 	default: Ref<ShaderMaterial, Shared>,
+	/// the materials for all opaque and all transparent voxel types, with every texture in a texture array
+	/// only built once the texture array is enabled
+	arrays: OnceLock<ArrayMaterials>,
+	use_array: AtomicBool,
+	use_smooth: AtomicBool,
+}
+
+struct ArrayMaterials {
+	opaque: Ref<ShaderMaterial, Shared>,
+	transparent: Ref<ShaderMaterial, Shared>,
 }
 
 impl MaterialList {
@@ -18,6 +36,9 @@ impl MaterialList {
 		let mut instance = Self {
 			mats: HashMap::new(),
 			default: load_mat_unsafe(DEFAULT_PATH),
+			arrays: OnceLock::new(),
+			use_array: AtomicBool::new(false),
+			use_smooth: AtomicBool::new(false),
 		};
 		instance.load();
 		instance
@@ -25,17 +46,16 @@ impl MaterialList {
 
 	fn load(&mut self) {
 		let resource_loader = ResourceLoader::godot_singleton();
-		for v in 0..256 {
-			let voxel = v as Voxel;
-			let path = format!("res://addons/voxel-engine/materials/voxels/{}.material", voxel.name());
-			if resource_loader.exists(&path, "ShaderMaterial") {
+		for (voxel, voxel_type) in registry().types() {
+			let path = &voxel_type.material;
+			if !path.is_empty() && resource_loader.exists(path, "ShaderMaterial") {
 				let mat = resource_loader
-					.load(&path, "ShaderMaterial", false)
+					.load(path, "ShaderMaterial", false)
 					.unwrap()
 					.cast::<ShaderMaterial>()
 					.unwrap();
 				self.mats.insert(voxel, mat);
-				godot_print!("loaded material: {}", &path);
+				godot_print!("loaded material: {}", path);
 			}
 		}
 	}
@@ -46,6 +66,50 @@ impl MaterialList {
 		}
 		self.default.clone()
 	}
+
+	/// the material shared by all opaque voxel types when using a texture array
+	pub fn get_array(&self) -> Ref<ShaderMaterial, Shared> {
+		self.array_materials().opaque.clone()
+	}
+
+	/// the material shared by all transparent voxel types when using a texture array
+	pub fn get_array_alpha(&self) -> Ref<ShaderMaterial, Shared> {
+		self.array_materials().transparent.clone()
+	}
+
+	fn array_materials(&self) -> &ArrayMaterials {
+		self.arrays.get_or_init(|| {
+			let textures = load_texture_array();
+			ArrayMaterials {
+				opaque: array_mat(ARRAY_SHADER_PATH, &textures),
+				transparent: array_mat(ARRAY_ALPHA_SHADER_PATH, &textures),
+			}
+		})
+	}
+
+	/// whether voxel types are meshed into shared opaque and transparent surfaces using the texture array
+	#[inline]
+	pub fn texture_array(&self) -> bool {
+		self.use_array.load(Ordering::Relaxed)
+	}
+
+	/// builds the texture array the first time it is enabled, so must be called on the main thread
+	pub fn set_texture_array(&self, enabled: bool) {
+		if enabled {
+			self.array_materials();
+		}
+		self.use_array.store(enabled, Ordering::Relaxed);
+	}
+
+	/// whether opaque voxels are meshed as a smooth surface instead of cubes
+	#[inline]
+	pub fn smooth(&self) -> bool {
+		self.use_smooth.load(Ordering::Relaxed)
+	}
+
+	pub fn set_smooth(&self, enabled: bool) {
+		self.use_smooth.store(enabled, Ordering::Relaxed);
+	}
 }
 
 fn load_mat_unsafe(path: &str) -> Ref<ShaderMaterial, Shared> {
@@ -55,3 +119,65 @@ fn load_mat_unsafe(path: &str) -> Ref<ShaderMaterial, Shared> {
 		.cast::<ShaderMaterial>()
 		.unwrap()
 }
+
+/// build a texture array from the textures in the registry, in layer order
+/// missing textures are replaced with magenta
+fn load_texture_array() -> Ref<TextureArray, Shared> {
+	let resource_loader = ResourceLoader::godot_singleton();
+	let mut images = Vec::new();
+	for name in registry().textures() {
+		let path = format!("{}/{}.png", TEXTURE_DIR, name);
+		let image = if resource_loader.exists(path.as_str(), "Texture") {
+			resource_loader
+				.load(path.as_str(), "Texture", false)
+				.and_then(|t| t.cast::<Texture>())
+				.and_then(|t| unsafe { t.assume_safe() }.get_data())
+		} else {
+			None
+		};
+		if image.is_none() {
+			godot_error!("missing voxel texture: {}", path);
+		}
+		images.push(image);
+	}
+	let size = images.iter()
+		.flatten()
+		.next()
+		.map(|i| unsafe { i.assume_safe() }.get_width())
+		.unwrap_or(DEFAULT_TEXTURE_SIZE);
+
+	let array = TextureArray::new();
+	array.create(size, size, images.len().max(1) as i64, Image::FORMAT_RGBA8, TextureLayered::FLAG_MIPMAPS | TextureLayered::FLAG_REPEAT);
+	for (layer, image) in images.into_iter().enumerate() {
+		let image = image.unwrap_or_else(|| {
+			let placeholder = Image::new();
+			placeholder.create(size, size, false, Image::FORMAT_RGBA8);
+			placeholder.fill(Color::from_rgb(1.0, 0.0, 1.0));
+			placeholder.into_shared()
+		});
+		let image = unsafe { image.assume_safe() };
+		if image.is_compressed() {
+			image.decompress().unwrap();
+		}
+		image.convert(Image::FORMAT_RGBA8);
+		if image.get_width() != size || image.get_height() != size {
+			image.resize(size, size, Image::INTERPOLATE_NEAREST);
+		}
+		image.generate_mipmaps(false).unwrap();
+		array.set_layer_data(image, layer as i64);
+	}
+	godot_print!("loaded {} voxel textures into texture array", registry().textures().len());
+	array.into_shared()
+}
+
+fn array_mat(shader_path: &str, textures: &Ref<TextureArray, Shared>) -> Ref<ShaderMaterial, Shared> {
+	let shader = ResourceLoader::godot_singleton()
+		.load(shader_path, "Shader", false)
+		.unwrap()
+		.cast::<Shader>()
+		.unwrap();
+	let material = ShaderMaterial::new();
+	material.set_shader(shader);
+	material.set_shader_param("textures", textures.clone());
+	material.into_shared()
+}
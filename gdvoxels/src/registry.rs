@@ -0,0 +1,226 @@
+use std::sync::OnceLock;
+
+use gdnative::prelude::*;
+use gdnative::api::{ConfigFile, Mesh};
+use voxel_core::shapes::*;
+use voxel_core::types::*;
+
+use crate::common::*;
+use crate::materials::TEXTURE_DIR;
+
+
+const TYPES_PATH: &str = "res://addons/voxel-engine/voxel_types.cfg";
+
+static REGISTRY: OnceLock<VoxelRegistry> = OnceLock::new();
+
+/// get the global voxel registry, loading it on first use
+/// loading also sets the voxel_core types, so this must be called before any chunks are generated
+#[inline]
+pub fn registry() -> &'static VoxelRegistry {
+	REGISTRY.get_or_init(VoxelRegistry::load)
+}
+
+/// the parts of a voxel type only Godot needs, the rest is in voxel_core's VoxelProperties
+pub struct VoxelType {
+	pub material: String,
+	/// arbitrary values for use in scripts
+	pub properties: Dictionary,
+}
+
+/// Voxel types indexed by id, loaded from voxel_types.cfg
+/// Each section in the file is one voxel type, named after the section:
+/// ```ini
+/// [leaves]
+/// id=5
+/// transparent=true
+/// solid=true
+/// selectable=true
+/// material="res://addons/voxel-engine/materials/voxels/leaves.material"
+/// texture="leaves"
+/// shape="cube"
+/// properties={ "flammable": true }
+/// ```
+/// Every key except id is optional.
+/// Shapes are cube, slab, stair, cross or custom. Custom shapes need a mesh resource path in model, with the voxel spanning 0..1.
+/// Shapes other than cubes can be turned with rotation, in quarter turns around the y axis.
+/// Textures are names of images in addons/voxel-engine/textures, and can be set per side with texture_top, texture_bottom and texture_side.
+/// Types without a texture, like air, use the first layer of the texture array.
+pub struct VoxelRegistry {
+	types: Vec<Option<VoxelType>>,
+	/// texture names, indexed by texture array layer
+	textures: Vec<String>,
+}
+
+
+impl VoxelRegistry {
+	fn load() -> Self {
+		let mut instance = Self {
+			types: Vec::new(),
+			textures: Vec::new(),
+		};
+		instance.insert(EMPTY, VoxelType {
+			material: String::new(),
+			properties: Dictionary::new_shared(),
+		});
+		let mut core_types = VoxelTypes::new();
+		instance.load_types(&mut core_types);
+		if set_voxel_types(core_types).is_err() {
+			godot_error!("voxel types were already set");
+		}
+		instance
+	}
+
+	fn load_types(&mut self, core_types: &mut VoxelTypes) {
+		let config = ConfigFile::new();
+		if config.load(TYPES_PATH).is_err() {
+			godot_error!("could not load voxel types from {}", TYPES_PATH);
+			return;
+		}
+		for section in config.get_sections().read().iter() {
+			let name = section.to_string();
+			let section = name.as_str();
+			let id = match config.get_value(section, "id", Variant::nil()).to::<i64>() {
+				Some(id) if (0..=Voxel::MAX as i64).contains(&id) => id as Voxel,
+				_ => {
+					godot_error!("voxel type {} needs an id between 0 and {}", name, Voxel::MAX);
+					continue;
+				}
+			};
+			let default_material = format!("res://addons/voxel-engine/materials/voxels/{}.material", name);
+			let (side, top, bottom) = if has_texture(&config, section, id) {
+				let texture: String = config.get_value(section, "texture", name.clone()).to().unwrap_or_default();
+				let side = config.get_value(section, "texture_side", texture.clone()).to().unwrap_or_default();
+				let top = config.get_value(section, "texture_top", texture.clone()).to().unwrap_or_default();
+				let bottom = config.get_value(section, "texture_bottom", texture).to().unwrap_or_default();
+				(self.layer(side), self.layer(top), self.layer(bottom))
+			}
+			else {
+				(0, 0, 0)
+			};
+			let shape_name: String = config.get_value(section, "shape", "cube").to().unwrap_or_default();
+			let shape = match Shape::from_name(&shape_name) {
+				Some(shape) => shape,
+				None => {
+					godot_error!("voxel type {} has unknown shape {}, using cube", name, shape_name);
+					Shape::Cube
+				}
+			};
+			let rotation = config.get_value(section, "rotation", 0).to::<i64>().unwrap_or(0).rem_euclid(4) as u8;
+			let model = match shape {
+				Shape::Custom => {
+					let path: String = config.get_value(section, "model", "").to().unwrap_or_default();
+					let mesh = ResourceLoader::godot_singleton()
+						.load(path.as_str(), "Mesh", false)
+						.and_then(|m| m.cast::<Mesh>());
+					match mesh {
+						Some(mesh) => build_model(shape, rotation, Some(mesh_model(&unsafe { mesh.assume_safe() }))),
+						None => {
+							godot_error!("could not load model {} for voxel type {}", path, name);
+							None
+						}
+					}
+				}
+				_ => build_model(shape, rotation, None),
+			};
+			// a custom shape without a model is meshed as a cube
+			let shape = if model.is_none() { Shape::Cube } else { shape };
+			let voxel_type = VoxelType {
+				material: config.get_value(section, "material", default_material).to().unwrap_or_default(),
+				properties: config.get_value(section, "properties", Variant::nil()).to().unwrap_or_default(),
+			};
+			godot_print!("loaded voxel type {}: {}", id, name);
+			core_types.insert(id, VoxelProperties {
+				transparent: config.get_value(section, "transparent", false).to().unwrap_or(false),
+				solid: config.get_value(section, "solid", true).to().unwrap_or(true),
+				selectable: config.get_value(section, "selectable", true).to().unwrap_or(true),
+				layers: [side, side, top, bottom, side, side],
+				shape,
+				model,
+				name,
+			});
+			self.insert(id, voxel_type);
+		}
+	}
+
+	fn insert(&mut self, id: Voxel, voxel_type: VoxelType) {
+		let index = id as usize;
+		if self.types.len() <= index {
+			self.types.resize_with(index + 1, || None);
+		}
+		self.types[index] = Some(voxel_type);
+	}
+
+	/// get the layer of a texture, adding it if it is new
+	fn layer(&mut self, texture: String) -> u16 {
+		match self.textures.iter().position(|t| *t == texture) {
+			Some(layer) => layer as u16,
+			None => {
+				self.textures.push(texture);
+				(self.textures.len() - 1) as u16
+			}
+		}
+	}
+
+	#[inline]
+	pub fn get(&self, id: Voxel) -> Option<&VoxelType> {
+		self.types.get(id as usize)?.as_ref()
+	}
+
+	pub fn types(&self) -> impl Iterator<Item = (Voxel, &VoxelType)> {
+		self.types.iter()
+			.enumerate()
+			.filter_map(|(id, t)| Some((id as Voxel, t.as_ref()?)))
+	}
+
+	/// texture names in texture array layer order
+	pub fn textures(&self) -> &[String] {
+		&self.textures
+	}
+}
+
+
+impl VoxelType {
+	pub fn info(&self, id: Voxel) -> Dictionary {
+		let info = Dictionary::new();
+		info.insert("id", id);
+		if let Some(core) = voxel_types().get(id) {
+			info.insert("name", &core.name);
+			info.insert("transparent", core.transparent);
+			info.insert("solid", core.solid);
+			info.insert("selectable", core.selectable);
+			info.insert("shape", format!("{:?}", core.shape).to_lowercase());
+			info.insert("layers", core.layers.iter().map(|&l| l as i32).collect::<Int32Array>());
+		}
+		info.insert("material", &self.material);
+		info.insert("properties", &self.properties);
+		info.into_shared()
+	}
+}
+
+
+/// air never gets a texture layer, and neither do types without texture keys when there is no image named after them
+fn has_texture(config: &ConfigFile, section: &str, id: Voxel) -> bool {
+	if id == EMPTY {
+		return false;
+	}
+	let keys = ["texture", "texture_side", "texture_top", "texture_bottom"];
+	if keys.iter().any(|&key| config.has_section_key(section, key)) {
+		return true;
+	}
+	ResourceLoader::godot_singleton().exists(format!("{}/{}.png", TEXTURE_DIR, section), "Texture")
+}
+
+
+/// triangles of the first surface of a mesh, see model_from_triangles
+fn mesh_model(mesh: &Mesh) -> Vec<ModelQuad> {
+	let arrays = mesh.surface_get_arrays(0);
+	let verts = arrays.get(Mesh::ARRAY_VERTEX as i32).to::<Vector3Array>().unwrap_or_default();
+	let normals = arrays.get(Mesh::ARRAY_NORMAL as i32).to::<Vector3Array>().unwrap_or_default();
+	let uvs = arrays.get(Mesh::ARRAY_TEX_UV as i32).to::<Vector2Array>().unwrap_or_default();
+	let indices = arrays.get(Mesh::ARRAY_INDEX as i32).to::<Int32Array>().unwrap_or_default();
+	let verts: Vec<Vec3> = verts.read().iter().map(|v| v.to_core()).collect();
+	let normals: Vec<Vec3> = normals.read().iter().map(|v| v.to_core()).collect();
+	let uvs: Vec<Vec2> = uvs.read().iter().map(|v| v.to_core()).collect();
+	let indices: Vec<usize> = indices.read().iter().map(|&i| i as usize).collect();
+	model_from_triangles(&verts, &normals, &uvs, &indices)
+}
@@ -0,0 +1,355 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::sync::atomic::{self, AtomicBool, AtomicU32};
+use std::sync::mpsc::Sender;
+use std::thread::{self, JoinHandle};
+use gdnative::prelude::*;
+use voxel_core::chunk::Neighbours;
+use voxel_core::storage::ChunkStorage;
+
+use crate::chunk::*;
+use crate::common::*;
+use crate::materials::MaterialList;
+use crate::terrain::*;
+
+
+/// the pool used by every world, alive as long as any world holds it
+static SHARED_POOL: Mutex<Weak<WorkerPool>> = Mutex::new(Weak::new());
+
+/// threads that generate and mesh chunks for any number of worlds
+/// jobs from all worlds share one queue, and the job closest to its world's player is done first
+pub struct WorkerPool {
+	shared: Arc<PoolShared>,
+	workers: Mutex<Vec<JoinHandle<()>>>,
+}
+
+/// the part of the pool the worker threads hold on to
+struct PoolShared {
+	queue: Mutex<JobQueue>,
+	job_added: Condvar,
+}
+
+/// jobs ordered by distance to their world's player
+/// cancelled jobs are only removed from `jobs` and `index`; their heap entries are skipped when they come up
+struct JobQueue {
+	heap: BinaryHeap<QueuedJob>,
+	jobs: HashMap<u64, Job>,
+	/// id of the queued job for each world and JobKey, for cancelling and replacing jobs
+	index: HashMap<(usize, JobKey), u64>,
+	next_id: u64,
+	exit: bool,
+}
+
+/// a heap entry, with the priority it had when the player had moved `moves` times
+struct QueuedJob {
+	priority: f32,
+	moves: u32,
+	id: u64,
+}
+
+/// what a job works on, a world has at most one queued job for each
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum JobKey {
+	Load(ChunkLoc, u8),
+	Optimise(ChunkLoc),
+}
+
+/// what the workers need from a world to do its jobs
+pub struct WorldJobs {
+	pub player_loc: Arc<Mutex<Vec3>>,
+	pub materials: Arc<MaterialList>,
+	pub storage: Arc<Mutex<ChunkStorage>>,
+	pub seed: i64,
+	pub finished_chunks: Sender<Chunk>,
+	pub optimised_chunks: Sender<OptimisedChunk>,
+	/// counts changes of player_loc, so queued jobs know their priority is outdated
+	pub player_moves: AtomicU32,
+	/// build collision faces for new chunks, set from the world's collision property
+	pub collision: AtomicBool,
+}
+
+struct Job {
+	world: Arc<WorldJobs>,
+	kind: JobKind,
+}
+
+enum JobKind {
+	/// load or generate a chunk, or generate a lod region when the level is above 0, then mesh it
+	/// chunks are meshed with the borders of their loaded neighbours, so they do not need remeshing once they are collected
+	Load(ChunkLoc, u8, Box<Neighbours>),
+	/// remesh an edited chunk
+	Optimise(ChunkSnapshot),
+}
+
+
+impl WorkerPool {
+	/// the pool shared by all worlds, a new one is started if no world is using one
+	pub fn shared() -> Arc<Self> {
+		let mut shared_pool = SHARED_POOL.lock().unwrap();
+		if let Some(pool) = shared_pool.upgrade() {
+			return pool;
+		}
+		let pool = Arc::new(Self {
+			shared: Arc::new(PoolShared {
+				queue: Mutex::new(JobQueue::new()),
+				job_added: Condvar::new(),
+			}),
+			workers: Mutex::new(Vec::new()),
+		});
+		*shared_pool = Arc::downgrade(&pool);
+		pool
+	}
+
+	/// start more workers if there are less than `count`; 0 means one per cpu core
+	/// the pool never shrinks, so a shared pool has as many workers as the world that asked for the most
+	pub fn ensure_workers(&self, count: usize) {
+		let count = if count == 0 {
+			thread::available_parallelism().map_or(1, |n| n.get())
+		} else {
+			count
+		};
+		let mut workers = self.workers.lock().unwrap();
+		while workers.len() < count {
+			let shared = self.shared.clone();
+			let handle = thread::Builder::new()
+				.name(format!("voxel worker {}", workers.len()))
+				.spawn(move || worker(shared))
+				.unwrap();
+			workers.push(handle);
+		}
+	}
+
+	/// queue a chunk to be loaded or generated and meshed, the result is sent to the world's finished_chunks
+	/// neighbours has the borders of the chunks around it that are loaded, lod regions do not use them
+	pub fn load(&self, world: &Arc<WorldJobs>, loc: ChunkLoc, level: u8, neighbours: Neighbours) {
+		self.shared.push(Job { world: world.clone(), kind: JobKind::Load(loc, level, Box::new(neighbours)) });
+	}
+
+	/// update the borders a queued chunk will be meshed with, after one of its neighbours loaded or changed
+	/// does nothing if the chunk is not queued, the world then finds the change when it collects the chunk
+	pub fn update_neighbours(&self, world: &Arc<WorldJobs>, loc: ChunkLoc, update: impl FnOnce(&mut Neighbours)) {
+		let mut queue = self.shared.queue.lock().unwrap();
+		let id = match queue.index.get(&(world_id(world), JobKey::Load(loc, 0))) {
+			Some(id) => *id,
+			None => return,
+		};
+		if let Some(Job { kind: JobKind::Load(_, _, neighbours), .. }) = queue.jobs.get_mut(&id) {
+			update(neighbours.as_mut());
+		}
+	}
+
+	/// queue an edited chunk to be optimised, the result is sent to the world's optimised_chunks
+	/// replaces an older snapshot of the same chunk that has not been started yet
+	pub fn optimise(&self, world: &Arc<WorldJobs>, snapshot: ChunkSnapshot) {
+		self.shared.push(Job { world: world.clone(), kind: JobKind::Optimise(snapshot) });
+	}
+
+	/// drop a queued load job; chunks that are already being worked on are still sent back
+	pub fn cancel(&self, world: &Arc<WorldJobs>, loc: ChunkLoc, level: u8) {
+		self.shared.queue.lock().unwrap().cancel(world_id(world), JobKey::Load(loc, level));
+	}
+
+	/// drop every queued job of a world
+	pub fn cancel_all(&self, world: &Arc<WorldJobs>) {
+		let mut queue = self.shared.queue.lock().unwrap();
+		let id = world_id(world);
+		let keys: Vec<JobKey> = queue.index.keys().filter(|(w, _)| *w == id).map(|(_, key)| *key).collect();
+		for key in keys {
+			queue.cancel(id, key);
+		}
+	}
+}
+
+impl Drop for WorkerPool {
+	fn drop(&mut self) {
+		self.shared.queue.lock().unwrap().exit = true;
+		self.shared.job_added.notify_all();
+		for handle in self.workers.lock().unwrap().drain(..) {
+			handle.join().ok();
+		}
+		godot_print!("Worker threads exited");
+	}
+}
+
+
+impl PoolShared {
+	fn push(&self, job: Job) {
+		self.queue.lock().unwrap().push(job);
+		self.job_added.notify_one();
+	}
+
+	/// wait for the next job, None if the pool is shutting down
+	fn next_job(&self) -> Option<Job> {
+		let mut queue = self.queue.lock().unwrap();
+		loop {
+			if queue.exit {
+				return None;
+			}
+			if let Some(job) = queue.pop() {
+				return Some(job);
+			}
+			queue = self.job_added.wait(queue).unwrap();
+		}
+	}
+}
+
+
+impl JobQueue {
+	fn new() -> Self {
+		Self {
+			heap: BinaryHeap::new(),
+			jobs: HashMap::new(),
+			index: HashMap::new(),
+			next_id: 0,
+			exit: false,
+		}
+	}
+
+	/// add a job, replacing the queued job for the same chunk if there is one
+	fn push(&mut self, job: Job) {
+		let key = (world_id(&job.world), job.key());
+		if let Some(id) = self.index.get(&key) {
+			// keeps its place in the heap, the priority only depends on the key
+			self.jobs.insert(*id, job);
+			return;
+		}
+		let id = self.next_id;
+		self.next_id += 1;
+		self.heap.push(QueuedJob {
+			priority: job.priority(),
+			moves: job.world.player_moves.load(atomic::Ordering::Relaxed),
+			id,
+		});
+		self.jobs.insert(id, job);
+		self.index.insert(key, id);
+	}
+
+	fn cancel(&mut self, world: usize, key: JobKey) {
+		if let Some(id) = self.index.remove(&(world, key)) {
+			self.jobs.remove(&id);
+		}
+		// drop the skipped entries once they are most of the heap
+		if self.heap.len() > self.jobs.len() * 2 + 64 {
+			let jobs = &self.jobs;
+			self.heap.retain(|entry| jobs.contains_key(&entry.id));
+		}
+	}
+
+	/// the closest job, re-prioritizing jobs whose player moved since they were queued on the way
+	fn pop(&mut self) -> Option<Job> {
+		while let Some(entry) = self.heap.pop() {
+			let job = match self.jobs.get(&entry.id) {
+				Some(job) => job,
+				// cancelled
+				None => continue,
+			};
+			let moves = job.world.player_moves.load(atomic::Ordering::Relaxed);
+			if entry.moves != moves {
+				self.heap.push(QueuedJob { priority: job.priority(), moves, id: entry.id });
+				continue;
+			}
+			let job = self.jobs.remove(&entry.id).unwrap();
+			self.index.remove(&(world_id(&job.world), job.key()));
+			return Some(job);
+		}
+		None
+	}
+}
+
+
+/// the heap is a max heap, so lower priorities compare as greater
+impl Ord for QueuedJob {
+	fn cmp(&self, other: &Self) -> Ordering {
+		other.priority.total_cmp(&self.priority)
+	}
+}
+
+impl PartialOrd for QueuedJob {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl PartialEq for QueuedJob {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other) == Ordering::Equal
+	}
+}
+
+impl Eq for QueuedJob {}
+
+
+impl WorldJobs {
+	/// call after changing player_loc, so queued jobs get re-prioritized
+	pub fn player_moved(&self) {
+		self.player_moves.fetch_add(1, atomic::Ordering::Relaxed);
+	}
+}
+
+
+impl Job {
+	fn key(&self) -> JobKey {
+		match &self.kind {
+			JobKind::Load(loc, level, _) => JobKey::Load(*loc, *level),
+			JobKind::Optimise(snapshot) => JobKey::Optimise(snapshot.loc),
+		}
+	}
+
+	/// lower is sooner
+	/// edited chunks are near the player and already visible, so they go first
+	fn priority(&self) -> f32 {
+		let player_loc = *self.world.player_loc.lock().unwrap();
+		match &self.kind {
+			JobKind::Load(loc, level, _) => (loc_to_locv(*loc) * (1 << level) as f32).distance_squared_to(player_loc),
+			JobKind::Optimise(_) => -1.0,
+		}
+	}
+}
+
+
+fn worker(shared: Arc<PoolShared>) {
+	// noise generators can not be shared between threads, so each worker has its own for each seed
+	let mut generators: HashMap<i64, GodotTerrain> = HashMap::new();
+
+	while let Some(job) = shared.next_job() {
+		let world = job.world;
+		match job.kind {
+			JobKind::Load(loc, level, neighbours) => {
+				let terrain_gen = generators.entry(world.seed).or_insert_with(|| new_terrain_generator(world.seed));
+				let mut new_chunk = if level == 0 {
+					let wpos = loc_to_wpos(loc);
+					let saved = world.storage.lock().unwrap().load(loc).unwrap_or_else(|e| {
+						// the region is not written to either, so the saved chunk is kept
+						godot_error!("could not load chunk {:?}: {}", loc, e);
+						None
+					});
+					let mut core = saved.unwrap_or_else(|| terrain_gen.generate(wpos));
+					core.set_neighbours(*neighbours);
+					Chunk::new(wpos, core)
+				}
+				else {
+					// lod regions always show the generated terrain, edits are only visible up close
+					let scale = 1 << level;
+					let core = terrain_gen.generate_scaled(loc_to_wpos(loc) * scale as f32, scale);
+					Chunk::new_lod(loc, level, core)
+				};
+				new_chunk.optimise(&world.materials);
+				if level == 0 && world.collision.load(atomic::Ordering::Relaxed) {
+					new_chunk.build_collision();
+				}
+				// the world may be gone already
+				world.finished_chunks.send(new_chunk).ok();
+			}
+			JobKind::Optimise(snapshot) => {
+				world.optimised_chunks.send(snapshot.optimise(&world.materials)).ok();
+			}
+		}
+	}
+}
+
+/// jobs of different worlds are told apart by the address of their WorldJobs
+#[inline]
+fn world_id(world: &Arc<WorldJobs>) -> usize {
+	Arc::as_ptr(world) as usize
+}
@@ -1,33 +1,41 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use std::sync::mpsc::{self, Sender, Receiver};
-use std::thread::{self, JoinHandle};
+use std::sync::atomic::{self, AtomicBool, AtomicU32};
+use std::sync::mpsc::{self, Receiver};
 use gdnative::prelude::*;
+use gdnative::api::ProjectSettings;
+use voxel_core::chunk::{ChunkCore, Neighbours};
+use voxel_core::movement;
+use voxel_core::raycast::{self, RayPool};
+use voxel_core::snapshot::WorldSnapshot;
+use voxel_core::storage::ChunkStorage;
 
-use crate::chunk::core::ChunkCore;
 use crate::common::*;
 use crate::chunk::*;
 use crate::materials::*;
+use crate::registry::registry;
 use crate::terrain::*;
+use crate::workers::*;
 
 
+/// lod regions go up to 8x8x8 chunks
+const MAX_LOD_LEVELS: u8 = 3;
+
 fn chunk_name(loc: ChunkLoc) -> String {
 	format!("Chunk{:?}", loc)
 }
 
-enum MeshCommand {
-	Generate(Chunk),
-	Cancel(ChunkLoc),
-	Exit,
+fn lod_name(level: u8, loc: ChunkLoc) -> String {
+	format!("Lod{}{:?}", level, loc)
 }
 
-/// Generate is a locv
-enum GeneratorCommand {
-	Generate(Vector3),
-	Cancel(ChunkLoc),
-	Exit,
+/// the region `levels` levels up that contains the chunk or region at loc
+fn lod_parent(loc: ChunkLoc, levels: u8) -> ChunkLoc {
+	(loc.0 >> levels, loc.1 >> levels, loc.2 >> levels)
 }
 
+
 #[derive(NativeClass)]
 #[inherit(Node)]
 pub struct VoxelWorld {
@@ -35,22 +43,60 @@ pub struct VoxelWorld {
 	load_distance: u16,
 	#[property]
 	auto_load: bool,
+	/// the most chunks and lod regions added to the scene each frame
 	#[property]
 	max_chunks_loaded: u16,
 	#[property]
 	max_chunks_unloaded: u16,
-	player_loc: Arc<Mutex<Vector3>>,
+	/// directory modified chunks are saved to; unmodified chunks are regenerated instead, eg. user://worlds/<name>
+	#[property]
+	save_dir: String,
+	/// mesh all opaque voxel types into one surface and all transparent ones into another, using a texture array built from the registry
+	/// reduces draw calls to one per chunk, plus one for the transparent pass
+	#[property]
+	texture_array: bool,
+	/// mesh opaque voxels as a smooth surface instead of cubes, eg. to show terrain as rolling hills
+	/// transparent voxels and other shapes are still meshed as they are
+	#[property]
+	smooth: bool,
+	/// number of lod levels past the full detail chunks, each doubling the size of its voxels, up to 3 (8x)
+	/// the chunks around the player are grouped into regions of 2x2x2 chunks, those into regions of 2x2x2 regions and so on,
+	/// and each region closer than half of load_distance at its level is shown with more detail
+	/// with 0, all chunks within load_distance are loaded at full detail
+	#[property]
+	lod_levels: u8,
+	/// threads used for generating and meshing chunks, and the most cast_rays splits its rays over; 0 uses one per cpu core
+	/// all worlds share one pool, which has as many threads as the world that asked for the most
+	#[property]
+	worker_threads: u16,
+	/// give loaded chunks a StaticBody with a concave shape, so physics bodies can stand on the terrain
+	/// lod regions never collide
+	#[property]
+	collision: bool,
+	player_loc: Arc<Mutex<Vec3>>,
 	chunks: HashMap<ChunkLoc, ChunkContainer>,
 	unoptimised_chunks: Vec<ChunkLoc>,
+	/// chunks whose collision shape is rebuilt at the end of the frame, so many edits in one frame only rebuild it once
+	collision_queue: Vec<ChunkLoc>,
 	unload_queue: Vec<ChunkLoc>,
+	/// (level, loc) of the lod regions, which are only used for showing the terrain and never change
+	lod_regions: HashMap<(u8, ChunkLoc), ChunkContainer>,
+	lod_unload_queue: Vec<(u8, ChunkLoc)>,
+	/// number of chunks and lod regions still loading inside each region, at its own level or below
+	/// kept in step by set_chunk, remove_chunk, set_lod and remove_lod, so lod_loading_in does not have to search
+	loading_in: HashMap<(u8, ChunkLoc), u32>,
+	/// edits to chunks that have not loaded yet, as (vposv, voxel) in the order they were made
+	pending_edits: HashMap<ChunkLoc, Vec<(Vec3, Voxel)>>,
 	materials: Arc<MaterialList>,
+	storage: Arc<Mutex<ChunkStorage>>,
 
 
-	gen_queue: Sender<GeneratorCommand>,
-	mesh_queue: Sender<MeshCommand>,
+	pool: Arc<WorkerPool>,
+	/// threads for cast_rays, started by its first call
+	ray_pool: Option<RayPool>,
+	jobs: Arc<WorldJobs>,
 	finished_chunks_recv: Receiver<Chunk>,
-	mesh_thread_handle: Option<JoinHandle<()>>,
-	gen_thread_handle: Option<JoinHandle<()>>,
+	optimised_chunks_recv: Receiver<OptimisedChunk>,
 }
 
 enum ChunkContainer {
@@ -63,35 +109,63 @@ enum ChunkContainer {
 #[methods]
 impl VoxelWorld {
 	fn new(_owner: &Node) -> Self {
-		let (gen_queue, gen_queue_recv) = mpsc::channel();
 		let (finished_chunks, finished_chunks_recv) = mpsc::channel();
-		let (mesh_queue, mesh_queue_recv) = mpsc::channel();
+		let (optimised_chunks, optimised_chunks_recv) = mpsc::channel();
 
-		let player_loc = Arc::new(Mutex::new(Vector3::ZERO));
+		let player_loc = Arc::new(Mutex::new(Vec3::ZERO));
 		let materials = Arc::new(MaterialList::new());
-		let gen_thread_handle = terrain_thread(gen_queue_recv, mesh_queue.clone(), player_loc.clone());
-		let mesh_thread_handle = mesh_thread(materials.clone(), mesh_queue_recv, finished_chunks, player_loc.clone());
+		let storage = Arc::new(Mutex::new(ChunkStorage::new()));
+		let jobs = Arc::new(WorldJobs {
+			player_loc: player_loc.clone(),
+			materials: materials.clone(),
+			storage: storage.clone(),
+			seed: 42,
+			finished_chunks,
+			optimised_chunks,
+			player_moves: AtomicU32::new(0),
+			collision: AtomicBool::new(true),
+		});
 
 		Self {
 			chunks: HashMap::new(),
 			unoptimised_chunks: Vec::new(),
+			collision_queue: Vec::new(),
 			unload_queue: Vec::new(),
+			lod_regions: HashMap::new(),
+			lod_unload_queue: Vec::new(),
+			loading_in: HashMap::new(),
+			pending_edits: HashMap::new(),
 			load_distance: 2,
 			max_chunks_loaded: 32,
 			max_chunks_unloaded: 64,
+			save_dir: "user://worlds/default".into(),
+			texture_array: false,
+			smooth: false,
+			lod_levels: 0,
+			worker_threads: 0,
+			collision: true,
 			auto_load: true,
 			player_loc,
-			gen_queue,
 			finished_chunks_recv,
-			mesh_queue,
+			optimised_chunks_recv,
 			materials,
-			gen_thread_handle: Some(gen_thread_handle),
-			mesh_thread_handle: Some(mesh_thread_handle),
+			storage,
+			pool: WorkerPool::shared(),
+			ray_pool: None,
+			jobs,
 		}
 	}
 
 	#[export]
 	fn _ready(&mut self, owner: TRef<Node>) {
+		let dir = ProjectSettings::godot_singleton().globalize_path(self.save_dir.as_str());
+		if let Err(e) = self.storage.lock().unwrap().set_dir(PathBuf::from(dir.to_string())) {
+			godot_error!("could not use save directory {}: {}", self.save_dir, e);
+		}
+		self.materials.set_texture_array(self.texture_array);
+		self.materials.set_smooth(self.smooth);
+		self.jobs.collision.store(self.collision, atomic::Ordering::Relaxed);
+		self.pool.ensure_workers(self.worker_threads as usize);
 		self.load_near();
 		owner.connect("tree_exiting", owner, "_quit", VariantArray::new_shared(), 0).unwrap();
 	}
@@ -99,16 +173,18 @@ impl VoxelWorld {
 	#[export]
 	fn _quit(&mut self, _owner: &Node) {
 		godot_print!("Exiting");
-		self.gen_queue.send(GeneratorCommand::Exit).unwrap();
-		self.mesh_queue.send(MeshCommand::Exit).unwrap();
-		self.mesh_thread_handle.take().map(JoinHandle::join);
-		self.gen_thread_handle.take().map(JoinHandle::join);
+		let locs: Vec<ChunkLoc> = self.chunks.keys().copied().collect();
+		for loc in locs {
+			self.save_chunk(loc);
+		}
+		self.pool.cancel_all(&self.jobs);
+		self.save_pending_edits();
 	}
 
 	#[export]
 	fn set_player_pos(&mut self, _owner: &Node, new_pos: Vector3) {
 		let mut changed = false;
-		let new_loc = wpos_to_locv(new_pos);
+		let new_loc = wpos_to_locv(new_pos.to_core());
 		{
 			let mut player_loc = self.player_loc.lock().unwrap();
 			if new_loc != *player_loc {
@@ -116,6 +192,9 @@ impl VoxelWorld {
 				changed = true;
 			}
 		}
+		if changed {
+			self.jobs.player_moved();
+		}
 		if changed && self.auto_load {
 			self.load_near();
 			self.queue_unload_far();
@@ -125,82 +204,112 @@ impl VoxelWorld {
 	#[export]
 	fn _process(&mut self, owner: &Node, _delta: f32) {
 		self.collect_chunks(owner);
+		self.collect_optimised();
 
 		self.unload();
 		self.optimise_chunks();
+		self.update_collisions();
 	}
 
-	/// casts ray through world, sees unloaded chunks as empty
+	/// casts ray through world, options is a dictionary of filters, see common::ray_filter
+	/// unloaded chunks are seen as empty, unless stop_at_unloaded is set
+	/// returns a dictionary with hit, the entry point as pos, the face normal, the voxel and the distance,
+	/// voxel_pos and previous_pos, the voxel the ray stopped at and the one it came from,
+	/// and unloaded, which is true if it stopped at a chunk that is not loaded
 	/// max_len is clamped to 0.001..65536.0
 	#[export]
-	fn cast_ray(&mut self, owner: &Node, source: Vector3, dir: Vector3, max_len: f32) -> Ray {
-		let dir = dir.normalized();
-		let max_len = max_len.clamp(0.001, 65536.0);
-		let stepped_dir = step(0.0, dir);
-		let mut ray_len = 0.0;
-		
-		while ray_len <= max_len {
-			let ray_pos = source + dir * ray_len;
-			let voxel = self.get_voxel(owner, ray_pos);
-			if voxel != EMPTY {
-				let normal = calc_normal(ray_pos);
-				return Ray::hit(ray_pos, normal, voxel, ray_len);
-			}
-			
-			let pos_in_voxel = fract(ray_pos);
-			// distance "forward" along each axis to the next plane intersection
-			let dist_to_next_plane = stepped_dir - pos_in_voxel;
-			// distance along the ray to next plane intersection for each axis
-			let deltas = dist_to_next_plane / dir;
-			// move the smallest of these distances, so that no voxel is skipped
-			ray_len += mincomp(deltas).max(0.0001);
-		}
+	fn cast_ray(&mut self, _owner: &Node, source: Vector3, dir: Vector3, max_len: f32, #[opt] options: Dictionary) -> Ray {
+		let filter = ray_filter(&options);
+		raycast::cast_ray_filtered(source.to_core(), dir.to_core(), max_len, &filter, |pos| self.loaded_voxel_at(pos)).into()
+	}
 
-		fn calc_normal(hit_pos: Vector3) -> Vector3 {
-			let pos_in_voxel = fract(hit_pos);
-			let centered = pos_in_voxel - Vector3::ONE*0.5;
-			let axis = centered.abs().max_axis();
-			axis.vec() * centered.sign()
+	/// casts a ray from each of origins along the matching dir, with the same options as cast_ray
+	/// the rays are split over up to worker_threads threads, which read the chunks they go through
+	/// returns a dictionary with the same keys as cast_ray, but each one holds an array with an entry per ray:
+	/// PoolByteArrays for hit and unloaded, a PoolIntArray for voxel, a PoolRealArray for distance and PoolVector3Arrays for the rest
+	/// the arrays are empty if origins and dirs have different lengths
+	#[export]
+	fn cast_rays(&mut self, _owner: &Node, origins: Vector3Array, dirs: Vector3Array, max_len: f32, #[opt] options: Dictionary) -> Rays {
+		if origins.len() != dirs.len() {
+			godot_error!("cast_rays got {} origins but {} dirs", origins.len(), dirs.len());
+			return Vec::new().into();
+		}
+		let rays: Vec<(Vec3, Vec3)> = origins.read().iter()
+			.zip(dirs.read().iter())
+			.map(|(origin, dir)| (origin.to_core(), dir.to_core()))
+			.collect();
+		let filter = ray_filter(&options);
+		if self.ray_pool.is_none() {
+			self.ray_pool = Some(RayPool::new(self.worker_threads as usize));
 		}
+		let snapshot = self.snapshot_for_rays(&rays, max_len);
+		self.ray_pool.as_ref().unwrap().cast_rays(&rays, max_len, &filter, |pos| snapshot.get_voxel(pos)).into()
+	}
 
-		Ray::miss(source + dir * max_len, max_len)
+	/// moves a box by velocity * delta, sliding along solid voxels, which all count as full cubes
+	/// chunks that are still loading are solid, so bodies do not fall through the terrain before it appears
+	/// while grounded, the box climbs ledges up to step_height high
+	/// returns a dictionary with the moved aabb, the motion, the remaining velocity, whether it is grounded and the normals it hit
+	#[export]
+	fn move_and_slide_aabb(&self, _owner: &Node, aabb: Aabb, velocity: Vector3, delta: f32, #[opt] step_height: f32) -> Motion {
+		movement::move_and_slide_aabb(aabb.to_core(), velocity.to_core(), delta, step_height, |pos| self.blocks_movement(pos)).into()
 	}
 
 	#[export]
 	fn set_voxel(&mut self, owner: &Node, wpos: Vector3, voxel: Voxel) {
+		let wpos = wpos.to_core();
 		let loc = wpos_to_loc(wpos);
+		let vposv = wpos_to_vposv(wpos);
 		
+		if self.chunks.get(&loc).map_or(true, ChunkContainer::is_waiting) {
+			// the chunk is still being generated or was never requested, the edit is applied when it loads
+			self.pending_edits.entry(loc).or_default().push((vposv, voxel));
+			return;
+		}
 		if self.chunks.contains_key(&loc) {
 			let materials = self.materials.clone();
 			let chunkc = self.get_chunk_mut(loc).unwrap();
-			let vposv = wpos_to_vposv(wpos);
 			if chunkc.is_ready() {
 				let old_voxel = chunkc.get_voxel(vposv);
 				chunkc.set_voxel(vposv, voxel);
-				chunkc.chunk_mut().unwrap().remesh_pos(&materials, vposv, old_voxel);
+				let chunk = chunkc.chunk_mut().unwrap();
+				chunk.remesh_pos(&materials, vposv, old_voxel);
 			}
 			else if chunkc.is_empty() {
 				let mut new_chunk = Chunk::new(wpos, ChunkCore::new());
 				new_chunk.set_voxel(vposv, voxel);
 				new_chunk.mark_empty(false);
+				self.fetch_borders(loc, &mut new_chunk);
 				new_chunk.mesh_fast(&materials);
 				self.spawn_chunk_node(owner, loc, &new_chunk);
-				self.chunks.insert(loc, ChunkContainer::Ready(new_chunk));
+				self.set_chunk(loc, ChunkContainer::Ready(new_chunk));
+			}
+			else {
+				return;
 			}
-			if !self.unoptimised_chunks.contains(&loc) {
-				self.unoptimised_chunks.push(loc);
+			self.queue_optimise(loc);
+			if self.collision {
+				self.queue_collision(loc);
+			}
+			for face in border_faces(vposv) {
+				self.update_neighbour_face(loc, vposv, face);
+			}
+			for diagonal in border_diagonals(vposv) {
+				self.update_neighbour_diagonal(loc, diagonal);
 			}
 		}
 	}
 
 	#[export]
 	fn get_voxel(&mut self, _owner: &Node, wpos: Vector3) -> Voxel {
-		let loc = wpos_to_loc(wpos);
-		let vpos = wpos_to_vposv(wpos);
-		if self.chunk_is_loaded(loc) {
-			return self.get_chunk(loc).unwrap().get_voxel(vpos)
-		}
-		EMPTY
+		self.voxel_at(wpos.to_core())
+	}
+
+	/// returns a dictionary with the name, transparency, solidity, selectability, material, texture layers and custom properties of a voxel type
+	/// or null if the type is not registered
+	#[export]
+	fn get_voxel_type_info(&self, _owner: &Node, voxel: Voxel) -> Option<Dictionary> {
+		registry().get(voxel).map(|t| t.info(voxel))
 	}
 
 	#[export]
@@ -223,8 +332,56 @@ impl VoxelWorld {
 		self.chunks.values().filter(|x| x.is_waiting()).count()
 	}
 
+	/// the loaded chunks the rays can go through
+	fn snapshot_for_rays(&self, rays: &[(Vec3, Vec3)], max_len: f32) -> WorldSnapshot<'_> {
+		let mut snapshot = WorldSnapshot::new();
+		for loc in raycast::chunks_near_rays(rays, max_len) {
+			match self.get_chunk(loc) {
+				Some(ChunkContainer::Ready(chunk)) => snapshot.insert(loc, Some(chunk.core())),
+				Some(ChunkContainer::Empty) => snapshot.insert(loc, None),
+				_ => (),
+			}
+		}
+		snapshot
+	}
+
+	/// None if the chunk is not loaded
+	fn loaded_voxel_at(&self, wpos: Vec3) -> Option<Voxel> {
+		match self.get_chunk(wpos_to_loc(wpos))? {
+			ChunkContainer::Waiting => None,
+			chunkc => Some(chunkc.get_voxel(wpos_to_vposv(wpos))),
+		}
+	}
+
+	fn voxel_at(&self, wpos: Vec3) -> Voxel {
+		let loc = wpos_to_loc(wpos);
+		let vpos = wpos_to_vposv(wpos);
+		if self.chunk_is_loaded(loc) {
+			return self.get_chunk(loc).unwrap().get_voxel(vpos)
+		}
+		EMPTY
+	}
+
+	fn blocks_movement(&self, wpos: Vec3) -> bool {
+		if self.chunk_is_loading(wpos_to_loc(wpos)) {
+			return true;
+		}
+		self.voxel_at(wpos).is_solid()
+	}
+
 	/// load chunks around player pos
 	fn load_near(&mut self) {
+		if self.lod_levels > 0 {
+			for (level, loc) in self.lod_layout() {
+				if level == 0 {
+					self.load_or_generate(loc_to_locv(loc));
+				}
+				else {
+					self.load_lod(level, loc);
+				}
+			}
+			return;
+		}
 		let center_chunk = *self.player_loc.lock().unwrap();
 		let radius = self.load_distance as i32;
 		
@@ -242,15 +399,25 @@ impl VoxelWorld {
 	fn queue_unload_far(&mut self) {
 		let player_loc = *self.player_loc.lock().unwrap();
 		
+		let layout: Option<HashSet<(u8, ChunkLoc)>> = (self.lod_levels > 0).then(|| self.lod_layout().into_iter().collect());
+		
 		// let mut to_unload = Vec::new();
 		let mut to_cancel = Vec::new();
 		for (loc, chunk) in self.chunks.iter() {
-			let delta = loc_to_locv(*loc) - player_loc;
-			let delta = delta.abs();
-			let dist = delta.x.max(delta.y).max(delta.z);
-			if dist > self.load_distance as f32 + 1.0 {
+			let far = match &layout {
+				Some(layout) => !layout.contains(&(0, *loc)),
+				None => {
+					let delta = loc_to_locv(*loc) - player_loc;
+					let delta = delta.abs();
+					let dist = delta.x.max(delta.y).max(delta.z);
+					dist > self.load_distance as f32 + 1.0
+				}
+			};
+			if far {
 				if chunk.is_ready() {
-					self.unload_queue.push(*loc);
+					if !self.unload_queue.contains(loc) {
+						self.unload_queue.push(*loc);
+					}
 				}
 				else {
 					to_cancel.push(*loc);
@@ -258,14 +425,37 @@ impl VoxelWorld {
 			}
 		}
 		for loc in to_cancel {
-			self.chunks.remove(&loc);
 			self.cancel_generation(loc);
 		}
+
+		let mut lod_to_cancel = Vec::new();
+		if let Some(layout) = &layout {
+			for (key, region) in self.lod_regions.iter() {
+				if !layout.contains(key) {
+					if region.is_ready() {
+						if !self.lod_unload_queue.contains(key) {
+							self.lod_unload_queue.push(*key);
+						}
+					}
+					else {
+						lod_to_cancel.push(*key);
+					}
+				}
+			}
+		}
+		for (level, loc) in lod_to_cancel {
+			self.cancel_lod(level, loc);
+		}
 	}
 
 	fn unload(&mut self) {
 		let mut count = 0;
+		let mut deferred = Vec::new();
 		while let Some(loc) = self.unload_queue.pop() {
+			if self.lod_levels > 0 && self.lod_loading_in(0, loc) {
+				deferred.push(loc);
+				continue;
+			}
 			if self.chunk_is_loaded(loc) {
 				unsafe {
 					self.get_chunk(loc)
@@ -276,31 +466,270 @@ impl VoxelWorld {
 					.assume_safe()
 					.queue_free();
 				}
+				self.save_chunk(loc);
 				count += 1;
 			}
-			self.chunks.remove(&loc);
+			self.remove_chunk(loc);
 			if count > self.max_chunks_unloaded {
 				break;
 			}
 		}
-		
+		self.unload_queue.extend(deferred);
+		self.unload_lod();
 	}
 
 	fn cancel_generation(&mut self, loc: ChunkLoc) {
-		self.gen_queue.send(GeneratorCommand::Cancel(loc)).unwrap();
-		self.mesh_queue.send(MeshCommand::Cancel(loc)).unwrap();
-		self.chunks.remove(&loc);
+		self.pool.cancel(&self.jobs, loc, 0);
+		self.remove_chunk(loc);
+	}
+
+	/// the chunks (level 0) and lod regions to show around the player, as (level, loc)
+	/// top level regions within load_distance are split into their 8 children while they are within half of it
+	fn lod_layout(&self) -> Vec<(u8, ChunkLoc)> {
+		let player = locv_to_loc(*self.player_loc.lock().unwrap());
+		let levels = self.lod_levels.min(MAX_LOD_LEVELS);
+		let radius = self.load_distance as i32;
+		let split_radius = (radius + 1) / 2;
+		let center = |level: u8| (player.0 >> level, player.1 >> level, player.2 >> level);
+		let dist = |a: ChunkLoc, b: ChunkLoc| (a.0 - b.0).abs().max((a.1 - b.1).abs()).max((a.2 - b.2).abs());
+
+		let mut stack = Vec::new();
+		let top = center(levels);
+		for x in -radius..(radius + 1) {
+			for y in -radius..(radius + 1) {
+				for z in -radius..(radius + 1) {
+					stack.push((levels, loc_add(top, (x, y, z))));
+				}
+			}
+		}
+		let mut layout = Vec::new();
+		while let Some((level, loc)) = stack.pop() {
+			if level > 0 && dist(loc, center(level)) <= split_radius {
+				for child in 0..8 {
+					let child = (loc.0 * 2 + ((child >> 2) & 1), loc.1 * 2 + ((child >> 1) & 1), loc.2 * 2 + (child & 1));
+					stack.push((level - 1, child));
+				}
+			}
+			else {
+				layout.push((level, loc));
+			}
+		}
+		layout
+	}
+
+	fn load_lod(&mut self, level: u8, loc: ChunkLoc) {
+		self.lod_unload_queue.retain(|key| *key != (level, loc));
+		if self.lod_regions.contains_key(&(level, loc)) {
+			return;
+		}
+		if GodotTerrain::lod_has_terrain(loc, level) {
+			self.set_lod((level, loc), ChunkContainer::Waiting);
+			self.pool.load(&self.jobs, loc, level, Neighbours::default());
+		}
+		else {
+			self.set_lod((level, loc), ChunkContainer::Empty);
+		}
+	}
+
+	fn cancel_lod(&mut self, level: u8, loc: ChunkLoc) {
+		self.pool.cancel(&self.jobs, loc, level);
+		self.remove_lod((level, loc));
+	}
+
+	/// unload lod regions that are no longer needed, once whatever replaces them has loaded
+	fn unload_lod(&mut self) {
+		let mut i = 0;
+		while i < self.lod_unload_queue.len() {
+			let (level, loc) = self.lod_unload_queue[i];
+			if self.lod_loading_in(level, loc) {
+				i += 1;
+				continue;
+			}
+			self.lod_unload_queue.swap_remove(i);
+			if let Some(ChunkContainer::Ready(region)) = self.remove_lod((level, loc)) {
+				unsafe { region.node.assume_safe().queue_free(); }
+			}
+		}
+	}
+
+	/// whether a chunk or lod region overlapping the region at (level, loc) is still loading
+	/// chunks and regions are kept until their replacements are ready, so no holes appear while moving
+	fn lod_loading_in(&self, level: u8, loc: ChunkLoc) -> bool {
+		// regions overlap if the smaller one is inside the larger one
+		// so either something inside this region is loading, or one of the regions it is in
+		if self.loading_in.contains_key(&(level, loc)) {
+			return true;
+		}
+		(level + 1..=MAX_LOD_LEVELS).any(|top| {
+			let parent = lod_parent(loc, top - level);
+			self.lod_regions.get(&(top, parent)).map_or(false, ChunkContainer::is_waiting)
+		})
+	}
+
+	/// update loading_in after the chunk or lod region at (level, loc) started or stopped loading
+	fn loading_changed(&mut self, level: u8, loc: ChunkLoc, was_loading: bool, loading: bool) {
+		if was_loading == loading {
+			return;
+		}
+		for top in level..=MAX_LOD_LEVELS {
+			let key = (top, lod_parent(loc, top - level));
+			if loading {
+				*self.loading_in.entry(key).or_insert(0) += 1;
+			}
+			else if let Some(count) = self.loading_in.get_mut(&key) {
+				*count -= 1;
+				if *count == 0 {
+					self.loading_in.remove(&key);
+				}
+			}
+		}
+	}
+
+	/// insert or replace a chunk, all changes to chunks go through this and remove_chunk
+	fn set_chunk(&mut self, loc: ChunkLoc, chunk: ChunkContainer) {
+		let loading = chunk.is_waiting();
+		let old = self.chunks.insert(loc, chunk);
+		self.loading_changed(0, loc, old.as_ref().map_or(false, ChunkContainer::is_waiting), loading);
+	}
+
+	fn remove_chunk(&mut self, loc: ChunkLoc) -> Option<ChunkContainer> {
+		let old = self.chunks.remove(&loc);
+		self.loading_changed(0, loc, old.as_ref().map_or(false, ChunkContainer::is_waiting), false);
+		old
+	}
+
+	/// insert or replace a lod region, all changes to lod regions go through this and remove_lod
+	fn set_lod(&mut self, key: (u8, ChunkLoc), region: ChunkContainer) {
+		let loading = region.is_waiting();
+		let old = self.lod_regions.insert(key, region);
+		self.loading_changed(key.0, key.1, old.as_ref().map_or(false, ChunkContainer::is_waiting), loading);
+	}
+
+	fn remove_lod(&mut self, key: (u8, ChunkLoc)) -> Option<ChunkContainer> {
+		let old = self.lod_regions.remove(&key);
+		self.loading_changed(key.0, key.1, old.as_ref().map_or(false, ChunkContainer::is_waiting), false);
+		old
+	}
+
+	fn queue_optimise(&mut self, loc: ChunkLoc) {
+		if !self.unoptimised_chunks.contains(&loc) {
+			self.unoptimised_chunks.push(loc);
+		}
+	}
+
+	fn queue_collision(&mut self, loc: ChunkLoc) {
+		if !self.collision_queue.contains(&loc) {
+			self.collision_queue.push(loc);
+		}
+	}
+
+	/// the borders of the loaded chunks around loc
+	fn neighbour_borders(&self, loc: ChunkLoc) -> Neighbours {
+		let mut neighbours = Neighbours::default();
+		for (face, offset) in NEIGHBOURS.iter().enumerate() {
+			if let Some(other) = self.get_chunk(loc_add(loc, *offset)).and_then(ChunkContainer::chunk) {
+				neighbours.set_face(face, other.border(opposite_face(face)));
+			}
+		}
+		for (diagonal, offset) in DIAGONALS.iter().enumerate() {
+			if let Some(other) = self.get_chunk(loc_add(loc, *offset)).and_then(ChunkContainer::chunk) {
+				neighbours.set_diagonal(diagonal, other.diagonal_opacity(DIAGONALS[opposite_diagonal(diagonal)]));
+			}
+		}
+		neighbours
+	}
+
+	/// get the borders of a chunk that is not in the world yet from its loaded neighbours
+	/// returns true if the chunk's borders changed, eg. because a neighbour loaded after it was queued
+	fn fetch_borders(&self, loc: ChunkLoc, chunk: &mut Chunk) -> bool {
+		chunk.set_neighbours(self.neighbour_borders(loc))
+	}
+
+	/// send the borders of a chunk to its neighbours
+	/// loaded neighbours that changed are queued for remeshing, and queued ones are meshed with the new borders
+	fn share_borders(&mut self, loc: ChunkLoc) {
+		for (face, offset) in NEIGHBOURS.iter().enumerate() {
+			let border = match self.get_chunk(loc).and_then(ChunkContainer::chunk) {
+				Some(chunk) => chunk.border(face),
+				None => return,
+			};
+			let other_loc = loc_add(loc, *offset);
+			match self.get_chunk_mut(other_loc) {
+				Some(ChunkContainer::Ready(other)) => {
+					if other.set_neighbour(opposite_face(face), border) {
+						self.queue_optimise(other_loc);
+					}
+				}
+				Some(ChunkContainer::Waiting) => {
+					self.pool.update_neighbours(&self.jobs, other_loc, |n| n.set_face(opposite_face(face), border));
+				}
+				_ => (),
+			}
+		}
+		for (diagonal, offset) in DIAGONALS.iter().enumerate() {
+			let mask = match self.get_chunk(loc).and_then(ChunkContainer::chunk) {
+				Some(chunk) => chunk.diagonal_opacity(*offset),
+				None => return,
+			};
+			let other_loc = loc_add(loc, *offset);
+			match self.get_chunk_mut(other_loc) {
+				Some(ChunkContainer::Ready(other)) => {
+					if other.set_diagonal(opposite_diagonal(diagonal), mask) {
+						self.queue_optimise(other_loc);
+					}
+				}
+				Some(ChunkContainer::Waiting) => {
+					self.pool.update_neighbours(&self.jobs, other_loc, |n| n.set_diagonal(opposite_diagonal(diagonal), mask));
+				}
+				_ => (),
+			}
+		}
+	}
+
+	/// after an edit at vposv on the border of a chunk, update the face of the touching voxel in the neighbouring chunk
+	fn update_neighbour_face(&mut self, loc: ChunkLoc, vposv: Vec3, face: usize) {
+		let border = match self.get_chunk(loc).and_then(ChunkContainer::chunk) {
+			Some(chunk) => chunk.border(face),
+			None => return,
+		};
+		let offset = NEIGHBOURS[face];
+		let other_loc = loc_add(loc, offset);
+		let other_vposv = (vposv + loc_to_locv(offset)).posmod(WIDTH_F);
+		let materials = self.materials.clone();
+		if let Some(other) = self.get_chunk_mut(other_loc).and_then(ChunkContainer::chunk_mut) {
+			if other.set_neighbour(opposite_face(face), border) {
+				other.remesh_face(&materials, other_vposv, opposite_face(face));
+				self.queue_optimise(other_loc);
+			}
+		}
 	}
 
+	/// after an edit on an edge or corner of a chunk, update the diagonal neighbour touching it
+	fn update_neighbour_diagonal(&mut self, loc: ChunkLoc, diagonal: usize) {
+		let offset = DIAGONALS[diagonal];
+		let mask = match self.get_chunk(loc).and_then(ChunkContainer::chunk) {
+			Some(chunk) => chunk.diagonal_opacity(offset),
+			None => return,
+		};
+		let other_loc = loc_add(loc, offset);
+		let materials = self.materials.clone();
+		if let Some(other) = self.get_chunk_mut(other_loc).and_then(ChunkContainer::chunk_mut) {
+			if other.set_diagonal(opposite_diagonal(diagonal), mask) {
+				other.remesh_diagonal(&materials);
+				self.queue_optimise(other_loc);
+			}
+		}
+	}
+
+	/// send chunks that have not changed for a while to the workers to be optimised
 	fn optimise_chunks(&mut self) {
 		let mut i = 0;
 		while i < self.unoptimised_chunks.len() {
 			let loc = self.unoptimised_chunks[i];
 			if self.chunk_is_loaded(loc) {
-				let materials = self.materials.clone();
-				let chunk = self.get_chunk_mut(loc).unwrap().chunk_mut().unwrap();
+				let chunk = self.get_chunk(loc).unwrap().chunk().unwrap();
 				if chunk.since_change().elapsed().as_millis() >= 1000 {
-					chunk.optimise(&materials);
+					self.pool.optimise(&self.jobs, chunk.snapshot());
 					self.unoptimised_chunks.swap_remove(i);
 				}
 				else {
@@ -312,23 +741,71 @@ impl VoxelWorld {
 		}
 	}
 
-	/// if chunk at loc is not already loaded, generate a new one
-	/// (todo) load from disk if it exists instead of generating
-	fn load_or_generate(&mut self, locv: Vector3) {
+	/// rebuild the collision shapes of the chunks edited this frame
+	/// chunks that were unloaded in the meantime are skipped
+	fn update_collisions(&mut self) {
+		for loc in std::mem::take(&mut self.collision_queue) {
+			if let Some(chunk) = self.get_chunk_mut(loc).and_then(ChunkContainer::chunk_mut) {
+				chunk.update_collision();
+			}
+		}
+	}
+
+	/// apply edits to chunks that never finished loading and save them, so the edits are not lost
+	fn save_pending_edits(&mut self) {
+		if self.pending_edits.is_empty() {
+			return;
+		}
+		let terrain_gen = new_terrain_generator(self.jobs.seed);
+		let mut storage = self.storage.lock().unwrap();
+		for (loc, edits) in self.pending_edits.drain() {
+			let mut core = match storage.load(loc) {
+				Ok(saved) => saved.unwrap_or_else(|| terrain_gen.generate(loc_to_wpos(loc))),
+				Err(e) => {
+					godot_error!("could not save edits to chunk {:?}: {}", loc, e);
+					continue;
+				}
+			};
+			for (vposv, voxel) in edits {
+				core.set_voxel(vposv, voxel);
+			}
+			core.modified = true;
+			if let Err(e) = storage.save(loc, &core) {
+				godot_error!("failed to save chunk {:?}: {}", loc, e);
+			}
+		}
+	}
+
+	/// save chunk to disk if it differs from the generated terrain
+	fn save_chunk(&self, loc: ChunkLoc) {
+		if let Some(chunk) = self.get_chunk(loc).and_then(ChunkContainer::chunk) {
+			if !chunk.is_modified() {
+				return;
+			}
+			if let Err(e) = self.storage.lock().unwrap().save(loc, chunk.core()) {
+				godot_error!("failed to save chunk {:?}: {}", loc, e);
+			}
+		}
+	}
+
+	/// if chunk at loc is not already loaded, load it from disk or generate a new one
+	fn load_or_generate(&mut self, locv: Vec3) {
 		let loc = locv_to_loc(locv);
 		if self.chunk_is_loaded(loc) || self.chunk_is_loading(loc) {
+			self.unload_queue.retain(|l| *l != loc);
 			return;
 		}
 		self.begin_generate_chunk(loc);
 	}
 
 	fn begin_generate_chunk(&mut self, loc: ChunkLoc) {
-		if TerrainGenerator::loc_has_terrain(loc) {
-			self.chunks.insert(loc, ChunkContainer::Waiting);
-			self.gen_queue.send(GeneratorCommand::Generate(loc_to_locv(loc))).unwrap();
+		let has_edits = self.pending_edits.contains_key(&loc);
+		if GodotTerrain::loc_has_terrain(loc) || has_edits || self.storage.lock().unwrap().has(loc) {
+			self.set_chunk(loc, ChunkContainer::Waiting);
+			self.pool.load(&self.jobs, loc, 0, self.neighbour_borders(loc));
 		}
 		else {
-			self.chunks.insert(loc, ChunkContainer::Empty);
+			self.set_chunk(loc, ChunkContainer::Empty);
 		}
 		if self.unload_queue.contains(&loc) {
 			let mut i = 0;
@@ -342,31 +819,106 @@ impl VoxelWorld {
 		}
 	}
 
+	/// add the chunks and lod regions the workers finished, up to max_chunks_loaded of them with meshes each frame
 	fn collect_chunks(&mut self, owner: &Node) {
 		let mut count = 0;
 		while let Ok(new_chunk) = self.finished_chunks_recv.try_recv() {
-			let loc = locv_to_loc(new_chunk.wpos / WIDTH_F);
-
-			if new_chunk.is_empty() {
-				self.chunks.insert(loc, ChunkContainer::Empty);
-				continue;
+			let spawned = if new_chunk.lod > 0 {
+				self.collect_lod(owner, new_chunk)
+			}
+			else {
+				self.collect_chunk(owner, new_chunk)
+			};
+			if spawned {
+				count += 1;
 			}
-			
-			self.spawn_chunk_node(owner, loc, &new_chunk);
-			self.chunks.insert(loc, ChunkContainer::Ready(new_chunk));
-			count += 1;
-
 			if count > self.max_chunks_loaded {
 				break;
 			}
 		}
 	}
 
+	/// chunks that were cancelled while they were being generated are dropped, like in collect_lod
+	/// returns false if the chunk was dropped or is empty and has no node
+	fn collect_chunk(&mut self, owner: &Node, mut new_chunk: Chunk) -> bool {
+		let loc = locv_to_loc(new_chunk.wpos / WIDTH_F);
+		if !self.chunk_is_loading(loc) {
+			// the chunk was cancelled, or another job for it already finished and must not be replaced
+			unsafe { new_chunk.node.assume_unique().free(); }
+			return false;
+		}
+		let edits = self.pending_edits.remove(&loc);
+
+		if new_chunk.is_empty() && edits.is_none() {
+			unsafe { new_chunk.node.assume_unique().free(); }
+			self.set_chunk(loc, ChunkContainer::Empty);
+			return false;
+		}
+		if let Some(edits) = &edits {
+			for &(vposv, voxel) in edits {
+				new_chunk.set_voxel(vposv, voxel);
+			}
+			new_chunk.mark_empty(false);
+		}
+		
+		// the worker meshed it with its neighbours' borders, this only finds the ones that changed since then
+		let borders_changed = self.fetch_borders(loc, &mut new_chunk);
+		if edits.is_some() {
+			// the mesh from the worker is from before the edits
+			new_chunk.mesh_fast(&self.materials);
+		}
+		if self.collision {
+			// rebuilt here if the edits cleared the faces from the worker
+			new_chunk.update_collision();
+		}
+		self.spawn_chunk_node(owner, loc, &new_chunk);
+		self.set_chunk(loc, ChunkContainer::Ready(new_chunk));
+		self.share_borders(loc);
+		if borders_changed || edits.is_some() {
+			self.queue_optimise(loc);
+		}
+		true
+	}
+
+	/// swap in chunks optimised by the workers
+	/// results for chunks that changed in the meantime are dropped, the change already queued them again
+	fn collect_optimised(&mut self) {
+		let materials = self.materials.clone();
+		while let Ok(optimised) = self.optimised_chunks_recv.try_recv() {
+			if let Some(chunk) = self.get_chunk_mut(optimised.loc).and_then(ChunkContainer::chunk_mut) {
+				chunk.apply_optimised(optimised, &materials);
+			}
+		}
+	}
+
+	/// regions that were cancelled while they were being generated are dropped
+	/// returns false if the region was dropped or is empty
+	fn collect_lod(&mut self, owner: &Node, new_region: Chunk) -> bool {
+		let key = (new_region.lod, new_region.loc);
+		let waiting = self.lod_regions.get(&key).map_or(false, ChunkContainer::is_waiting);
+		if !waiting || new_region.is_empty() {
+			unsafe { new_region.node.assume_unique().free(); }
+			if waiting {
+				self.set_lod(key, ChunkContainer::Empty);
+			}
+			return false;
+		}
+		self.spawn_chunk_node(owner, new_region.loc, &new_region);
+		self.set_lod(key, ChunkContainer::Ready(new_region));
+		true
+	}
+
 	fn spawn_chunk_node(&mut self, owner: &Node, loc: ChunkLoc, new_chunk: &Chunk) {
 		let mesh = unsafe {new_chunk.node.assume_safe()};
 		mesh.set_mesh(new_chunk.array_mesh());
-		mesh.set_translation(new_chunk.wpos);
-		mesh.set_name(chunk_name(loc));
+		mesh.set_translation(new_chunk.wpos.to_godot());
+		mesh.set_scale(Vector3::ONE * new_chunk.scale());
+		if new_chunk.lod > 0 {
+			mesh.set_name(lod_name(new_chunk.lod, loc));
+		}
+		else {
+			mesh.set_name(chunk_name(loc));
+		}
 		let mesh = unsafe { mesh.assume_shared() };
 		owner.add_child(mesh, false);
 	}
@@ -396,101 +948,6 @@ impl VoxelWorld {
 	}
 }
 
-fn terrain_thread(
-	gen_queue_recv: Receiver<GeneratorCommand>,
-	mesh_queue_terrain: Sender<MeshCommand>,
-	player_pos: Arc<Mutex<Vector3>>
-) -> JoinHandle<()> {
-	thread::Builder::new().name("terrain".to_string()).spawn(move || {
-		
-		let terrain_gen = TerrainGenerator::new(42);
-		let mut queue = Vec::new();
-
-		'mainloop: loop {
-			let mut recieved = if queue.is_empty() {
-				// if queue is empty, block thread until more chunks are requested to save cpu
-				gen_queue_recv.recv().ok()
-			} else {
-				gen_queue_recv.try_recv().ok()
-			};
-			while let Some(cmd) = recieved.take() {
-				match cmd {
-					GeneratorCommand::Exit => break 'mainloop,
-					GeneratorCommand::Cancel(loc) => {
-						let locv = loc_to_locv(loc);
-						for i in 0..queue.len() {
-							if queue[i] == locv {
-								queue.remove(i);
-								break;
-							}
-						}
-					},
-					GeneratorCommand::Generate(pos) => queue.push(pos),
-				}
-				recieved = gen_queue_recv.try_recv().ok();
-			}
-			if queue.is_empty() {continue;}
-			// sort so closest chunk is at the end
-			let player_loc = *player_pos.lock().unwrap();
-			queue.sort_by(|a, b| a.distance_squared_to(player_loc).partial_cmp(&b.distance_squared_to(player_loc)).unwrap());
-			
-			let locv = queue.remove(0);
-			let wpos = locv_to_wpos(locv);
-			let new_chunk = Chunk::new(wpos, terrain_gen.generate(wpos));
-			mesh_queue_terrain.send(MeshCommand::Generate(new_chunk)).unwrap();
-		}
-		godot_print!("Terrain thread exiting");
-	}).unwrap()
-}
-
-fn mesh_thread(
-	materials: Arc<MaterialList>,
-	mesh_queue_recv: Receiver<MeshCommand>,
-	finished_chunks: Sender<Chunk>,
-	player_loc: Arc<Mutex<Vector3>>
-) -> JoinHandle<()>{
-	thread::Builder::new().name("mesh".to_string()).spawn(move || {
-
-		let mut queue = Vec::new();
-		
-		'mainloop: loop {
-			let mut recieved = if queue.is_empty() {
-				// if queue is empty, block thread until more chunks are requested to save cpu
-				mesh_queue_recv.recv().ok()
-			} else {
-				mesh_queue_recv.try_recv().ok()
-			};
-			while let Some(cmd) = recieved.take() {
-				match cmd {
-					MeshCommand::Exit => break 'mainloop,
-					MeshCommand::Generate(chunk) => queue.push(chunk),
-					MeshCommand::Cancel(loc) => {
-						let locv = loc_to_locv(loc);
-						for i in 0..queue.len() {
-							if wpos_to_locv(queue[i].wpos) == locv {
-								queue.remove(i);
-								break;
-							}
-						}
-					}
-				}
-				recieved = mesh_queue_recv.try_recv().ok();
-			}
-			if queue.is_empty() {continue;}
-			// sort so closest chunk is at the end
-			let player = *player_loc.lock().unwrap() * WIDTH_F;
-			queue.sort_by(|a, b| a.wpos.distance_squared_to(player).partial_cmp(&b.wpos.distance_squared_to(player)).unwrap());
-
-			let mut chunk = queue.remove(0);
-			chunk.optimise(&materials);
-			finished_chunks.send(chunk).unwrap();
-
-		}
-		godot_print!("Mesh thread exiting");
-	}).unwrap()
-}
-
-
 impl ChunkContainer {
 	fn chunk_mut(&mut self) -> Option<&mut Chunk> {
 		match self {
@@ -506,7 +963,7 @@ impl ChunkContainer {
 		}
 	}
 
-	fn get_voxel(&self, vpos: Vector3) -> Voxel{
+	fn get_voxel(&self, vpos: Vec3) -> Voxel{
 		match self {
 			Self::Waiting => EMPTY,
 			Self::Ready(chunk) => chunk.get_voxel(vpos),
@@ -514,7 +971,7 @@ impl ChunkContainer {
 		}
 	}
 	
-	fn set_voxel(&mut self, vpos: Vector3, voxel: Voxel) {
+	fn set_voxel(&mut self, vpos: Vec3, voxel: Voxel) {
 		if let Self::Ready(chunk) = self {
 			chunk.set_voxel(vpos, voxel);
 		}
@@ -541,26 +998,3 @@ impl ChunkContainer {
 		false
 	}
 }
-
-
-#[inline]
-fn fract(v: Vector3) -> Vector3 {
-	Vector3::new(
-		(v.x.fract() + 1.0).fract(),
-		(v.y.fract() + 1.0).fract(),
-		(v.z.fract() + 1.0).fract()
-	)
-}
-
-#[inline]
-fn mincomp(v: Vector3) -> f32 {
-	v.x.min(v.y.min(v.z))
-}
-
-fn step(e: f32, v: Vector3) -> Vector3 {
-	Vector3::new(
-		(v.x >= e) as u8 as f32, 
-		(v.y >= e) as u8 as f32, 
-		(v.z >= e) as u8 as f32,
-	)
-}
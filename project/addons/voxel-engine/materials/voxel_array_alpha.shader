@@ -0,0 +1,36 @@
+shader_type spatial;
+render_mode depth_draw_always;
+//render_mode cull_disabled;
+//render_mode unshaded;
+
+// built from the voxel registry by the engine, one layer per texture
+uniform sampler2DArray textures;
+
+uniform float disable_debug_uv;
+
+void fragment() {
+	vec3 cam_pos = CAMERA_MATRIX[3].xyz;
+	vec3 pos = (CAMERA_MATRIX * vec4(VERTEX, 1.0)).xyz;
+	float dist = length(cam_pos - pos);
+	float lod = dist/48.0;
+	// uvs are in voxels, so each voxel gets a whole texture
+	// vertex color alpha holds the texture layer
+	vec3 uv = vec3(fract(UV), round(COLOR.a));
+
+	vec4 color = textureLod(textures, uv, lod);
+
+	float uv_vis = 1.0;
+	if (UV2.x > 0.98 ||
+		UV2.x < 0.02 ||
+		UV2.y > 0.98 ||
+		UV2.y < 0.02 ||
+		abs(UV2.x - UV2.y) < 0.02) {
+		uv_vis = 0.5;
+	}
+	if (disable_debug_uv < 0.1) {
+		uv_vis = 1.0;
+	}
+	// vertex color rgb holds ambient occlusion
+	ALBEDO = color.rgb * color.rgb * uv_vis * COLOR.rgb;
+	ALPHA = color.a;
+}
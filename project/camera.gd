@@ -4,9 +4,21 @@ export var speed_base = 10
 export var speed_mod = 5.0
 export var sensitivity_h = 1.0
 export var sensitivity_v = 1.0
+export var walk_speed = 5.0
+export var jump_speed = 8.0
+export var gravity = 25.0
+export var step_height = 1.0
+# move through voxels without gravity, toggled with F
+export var fly = false
+export var body_size = Vector3(0.6, 1.8, 0.6)
+export var eye_height = 1.6
+
+onready var world = $"/root/Main/VoxelWorld"
 
 var paused := false
 var speed_current: float
+var velocity := Vector3()
+var grounded := false
 
 func _ready():
 	Input.set_mouse_mode(Input.MOUSE_MODE_CAPTURED)
@@ -18,6 +30,9 @@ func _input(event):
 		rotate_object_local(Vector3(1,0,0), angle_x)
 		var angle_y = -event.relative.x * sensitivity_h * 0.002
 		rotate_y(angle_y)
+	if event is InputEventKey and event.pressed and !event.echo and event.scancode == KEY_F:
+		fly = !fly
+		velocity = Vector3()
 
 func _physics_process(delta):
 	speed_current = speed_base
@@ -43,11 +58,27 @@ func _physics_process(delta):
 		dir += Vector3(-1,0,0)
 	if Input.is_key_pressed(KEY_D):
 		dir += Vector3(1,0,0)
-	if Input.is_key_pressed(KEY_Q):
-		dir += Vector3(0,-1,0)
-	if Input.is_key_pressed(KEY_E):
-		dir += Vector3(0,1,0)
 
-	var vel = dir.normalized() * speed_current
+	if fly:
+		if Input.is_key_pressed(KEY_Q):
+			dir += Vector3(0,-1,0)
+		if Input.is_key_pressed(KEY_E):
+			dir += Vector3(0,1,0)
+		translate(dir.normalized() * speed_current * delta)
+		return
+
+	# walk along the ground in the direction the camera faces
+	var horizontal = dir.normalized().rotated(Vector3.UP, rotation.y) * walk_speed
+	if Input.is_key_pressed(KEY_SHIFT):
+		horizontal *= 2
+	velocity.x = horizontal.x
+	velocity.z = horizontal.z
+	velocity.y -= gravity * delta
+	if grounded and Input.is_key_pressed(KEY_SPACE):
+		velocity.y = jump_speed
 
-	translate(vel*delta)
+	var box = AABB(translation - Vector3(body_size.x*0.5, eye_height, body_size.z*0.5), body_size)
+	var result = world.move_and_slide_aabb(box, velocity, delta, step_height)
+	translation += result.motion
+	velocity = result.velocity
+	grounded = result.grounded
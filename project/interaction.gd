@@ -23,12 +23,12 @@ func _process(delta):
 
 	if Input.is_action_just_pressed("place"):
 		if result.hit:
-			if world.get_voxel(result.pos + result.normal*0.5) == 0:
-				world.set_voxel(result.pos + result.normal*0.5, vtype)
+			if world.get_voxel(result.previous_pos) == 0:
+				world.set_voxel(result.previous_pos, vtype)
 
 	if Input.is_action_just_pressed("break"):
 		if result.hit:
-			world.set_voxel(result.pos - result.normal*0.4, 0)
+			world.set_voxel(result.voxel_pos, 0)
 
 	if Input.is_action_just_released("next_item"):
 		vtype = ((vtype + 255) % 255) + 1
@@ -37,8 +37,8 @@ func _process(delta):
 
 
 func raycast():
-	var result = world.cast_ray(player.translation, forward(), 12.0)
-	indicator.translation = (result.pos - result.normal*0.5).floor()
+	var result = world.cast_ray(player.translation, forward(), 12.0, {"ignore_unselectable": true})
+	indicator.translation = result.voxel_pos
 	indicator.visible = result.hit && enable_highlight
 	return result
 
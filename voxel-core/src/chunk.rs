@@ -0,0 +1,633 @@
+use crate::common::*;
+use crate::math::Vec3;
+
+
+#[derive(Clone)]
+pub struct ChunkCore {
+	pub empty: bool,
+	/// differs from what the terrain generator would produce, so it needs to be saved
+	pub modified: bool,
+	voxels: Storage,
+	neighbours: Neighbours,
+}
+
+/// the voxel layers touching this chunk in the 6 neighbouring chunks, in face order
+/// and the opacity of the edges and corners touching it in the diagonal chunks, in DIAGONALS order
+/// chunks that are not loaded count as transparent
+#[derive(Default, Clone, PartialEq)]
+pub struct Neighbours {
+	faces: [Border; 6],
+	/// one bit per voxel along the edge, in x, y, z order; corners only use the first bit
+	diagonals: [u32; 20],
+}
+
+/// the outermost layer of voxels on one face of a chunk, as seen by the neighbouring chunk
+/// rows and bits are the two axes along the face, in x, y, z order
+#[derive(Default, Clone, PartialEq)]
+pub struct Border {
+	/// 32 rows of 32 bits, a set bit means opaque
+	opaque: [u32; WIDTH],
+	/// voxel types, indexed by row * WIDTH + bit
+	/// only kept when the layer has transparent voxels other than air, so faces between them can be culled
+	transparent: Option<Box<[Voxel]>>,
+}
+
+/// in-memory representation of a chunk's voxels
+#[derive(Clone)]
+enum Storage {
+	/// every voxel in the chunk is the same
+	Uniform(Voxel),
+	Paletted(PalettedVoxels),
+}
+
+/// voxels stored as indices into a palette, packed into `bits` bits each
+#[derive(Clone)]
+struct PalettedVoxels {
+	palette: Vec<Voxel>,
+	bits: usize,
+	data: Box<[u64]>,
+}
+
+
+impl ChunkCore {
+	pub fn new() -> Self {
+		Self::new_filled(EMPTY)
+	}
+
+	pub fn new_filled(v: Voxel) -> Self {
+		Self {
+			empty: v == EMPTY,
+			modified: false,
+			voxels: Storage::Uniform(v),
+			neighbours: Neighbours::default(),
+		}
+	}
+
+	#[inline]
+	pub fn get_voxel(&self, vposv: Vec3) -> Voxel {
+		if vposv_in_bounds(vposv) {
+			return self.get_voxel_unsafe(vposv);
+		}
+		EMPTY
+	}
+
+	#[inline]
+	pub fn get_voxel_i(&self, vpos: VoxelPos) -> Voxel {
+		if vpos_in_bounds(vpos) {
+			return self.voxels.get(vpos_to_index(vpos));
+		}
+		EMPTY
+	}
+
+	#[inline]
+	pub fn get_voxel_unsafe(&self, pos: Vec3) -> Voxel {
+		self.voxels.get(vposv_to_index(pos))
+	}
+
+	#[inline]
+	pub fn get_voxel_index(&self, index: usize) -> Voxel {
+		self.voxels.get(index)
+	}
+
+	/// every voxel type the chunk stores, may include some that are no longer used until it is compacted
+	pub fn palette(&self) -> &[Voxel] {
+		match &self.voxels {
+			Storage::Uniform(voxel) => std::slice::from_ref(voxel),
+			Storage::Paletted(paletted) => &paletted.palette,
+		}
+	}
+
+	/// index into palette() of the voxel at index
+	#[inline]
+	pub fn palette_index(&self, index: usize) -> usize {
+		match &self.voxels {
+			Storage::Uniform(_) => 0,
+			Storage::Paletted(paletted) => paletted.index(index),
+		}
+	}
+
+	/// calls f with the index of every voxel and its index into palette(), in index order
+	#[inline]
+	pub fn for_each_palette_index(&self, mut f: impl FnMut(usize, usize)) {
+		match &self.voxels {
+			Storage::Uniform(_) => (0..VOLUME).for_each(|i| f(i, 0)),
+			// a constant bit count lets the compiler unroll the loop over each word
+			Storage::Paletted(paletted) => match paletted.bits {
+				1 => paletted.for_each_index::<1>(f),
+				2 => paletted.for_each_index::<2>(f),
+				4 => paletted.for_each_index::<4>(f),
+				8 => paletted.for_each_index::<8>(f),
+				_ => paletted.for_each_index::<16>(f),
+			},
+		}
+	}
+
+	#[inline]
+	pub fn set_voxel(&mut self, pos: Vec3, voxel: Voxel) {
+		if vposv_in_bounds(pos) {
+			self.set_voxel_unsafe(pos, voxel);
+		}
+	}
+
+	#[inline]
+	pub fn set_voxel_unsafe(&mut self, pos: Vec3, voxel: Voxel) {
+		self.voxels.set(vposv_to_index(pos), voxel);
+	}
+
+	/// whether the voxel at vpos is opaque; also works for voxels in the neighbouring chunks that touch this one
+	#[inline]
+	pub fn is_opaque_at(&self, vpos: VoxelPos) -> bool {
+		if vpos_in_bounds(vpos) {
+			return self.get_voxel_i(vpos).is_opaque();
+		}
+		if let Some((face, row, bit)) = border_pos(vpos) {
+			return self.neighbours.faces[face].is_opaque(row, bit);
+		}
+		match diagonal_pos(vpos) {
+			Some((diagonal, bit)) => (self.neighbours.diagonals[diagonal] >> bit) & 1 == 1,
+			None => false,
+		}
+	}
+
+	/// whether the voxel at vpos hides a face of a `voxel` touching it
+	/// opaque voxels hide every face, transparent cubes only hide faces of their own type
+	#[inline]
+	pub fn hides_face(&self, voxel: Voxel, vpos: VoxelPos) -> bool {
+		if vpos_in_bounds(vpos) {
+			let other = self.get_voxel_i(vpos);
+			return other.is_opaque() || (other == voxel && voxel != EMPTY && voxel.is_cube());
+		}
+		match border_pos(vpos) {
+			Some((face, row, bit)) => {
+				let border = &self.neighbours.faces[face];
+				border.is_opaque(row, bit) || (border.voxel(row, bit) == Some(voxel) && voxel != EMPTY && voxel.is_cube())
+			}
+			None => false,
+		}
+	}
+
+	/// the outermost layer of voxels on one face, for use as a neighbour's border
+	pub fn border(&self, face: usize) -> Border {
+		let layer = if face.is_multiple_of(2) { WIDTH_I8 - 1 } else { 0 };
+		let mut border = Border::default();
+		let mut voxels = vec![EMPTY; AREA];
+		let mut has_transparent = false;
+		for row in 0..WIDTH_I8 {
+			for bit in 0..WIDTH_I8 {
+				let vpos = match face {
+					0 | 1 => (layer, row, bit),
+					2 | 3 => (row, layer, bit),
+					_ => (row, bit, layer),
+				};
+				let voxel = self.get_voxel_i(vpos);
+				if voxel.is_opaque() {
+					border.opaque[row as usize] |= 1 << bit;
+				}
+				else if voxel != EMPTY {
+					has_transparent = true;
+				}
+				voxels[row as usize * WIDTH + bit as usize] = voxel;
+			}
+		}
+		if has_transparent {
+			border.transparent = Some(voxels.into_boxed_slice());
+		}
+		border
+	}
+
+	/// replace the borders of every neighbour at once, eg. with the ones gathered for a chunk before it was generated
+	/// returns true if any of them changed
+	pub fn set_neighbours(&mut self, neighbours: Neighbours) -> bool {
+		let changed = self.neighbours != neighbours;
+		self.neighbours = neighbours;
+		changed
+	}
+
+	/// update the border of the neighbouring chunk on a face, returns true if it changed
+	pub fn set_neighbour(&mut self, face: usize, border: Border) -> bool {
+		let changed = self.neighbours.faces[face] != border;
+		self.neighbours.faces[face] = border;
+		changed
+	}
+
+	/// opacity of the neighbour's layer touching a face, with rows and bits like in Border
+	#[inline]
+	pub fn neighbour_opacity(&self, face: usize) -> &[u32; WIDTH] {
+		&self.neighbours.faces[face].opaque
+	}
+
+	/// opacity of the voxels along the edge or at the corner of this chunk that touches the diagonal neighbour at offset
+	pub fn diagonal_opacity(&self, offset: ChunkLoc) -> u32 {
+		let side = |o: i32| if o == 1 { WIDTH_I8 - 1 } else { 0 };
+		let mut mask = 0;
+		let length = if offset.0 != 0 && offset.1 != 0 && offset.2 != 0 { 1 } else { WIDTH_I8 };
+		for bit in 0..length {
+			let vpos = (
+				if offset.0 == 0 { bit } else { side(offset.0) },
+				if offset.1 == 0 { bit } else { side(offset.1) },
+				if offset.2 == 0 { bit } else { side(offset.2) },
+			);
+			if self.get_voxel_i(vpos).is_opaque() {
+				mask |= 1 << bit;
+			}
+		}
+		mask
+	}
+
+	/// update the opacity of a diagonal neighbour's edge or corner, returns true if it changed
+	pub fn set_diagonal(&mut self, diagonal: usize, mask: u32) -> bool {
+		let changed = self.neighbours.diagonals[diagonal] != mask;
+		self.neighbours.diagonals[diagonal] = mask;
+		changed
+	}
+
+	/// drop unused palette entries and switch to the smallest representation that fits
+	pub fn compact(&mut self) {
+		if let Storage::Paletted(paletted) = &self.voxels {
+			if let Some(compacted) = paletted.compacted() {
+				self.voxels = compacted;
+			}
+		}
+	}
+
+	/// run-length encode the voxels as (run length: u16, voxel: u16) pairs
+	pub fn serialize(&self) -> Vec<u8> {
+		let mut data = Vec::new();
+		let mut run_voxel = self.voxels.get(0);
+		let mut run_len: u16 = 0;
+		for i in 0..VOLUME {
+			let voxel = self.voxels.get(i);
+			if voxel != run_voxel {
+				data.extend_from_slice(&run_len.to_le_bytes());
+				data.extend_from_slice(&run_voxel.to_le_bytes());
+				run_voxel = voxel;
+				run_len = 0;
+			}
+			run_len += 1;
+		}
+		data.extend_from_slice(&run_len.to_le_bytes());
+		data.extend_from_slice(&run_voxel.to_le_bytes());
+		data
+	}
+
+	/// returns None if the data does not describe exactly one chunk
+	pub fn deserialize(data: &[u8]) -> Option<Self> {
+		Self::deserialize_runs(data.chunks_exact(4).map(|run| {
+			(u16::from_le_bytes([run[0], run[1]]), Voxel::from_le_bytes([run[2], run[3]]))
+		}))
+	}
+
+	/// read chunks saved before voxel ids were widened, as (run length: u16, voxel: u8) triplets
+	pub fn deserialize_u8(data: &[u8]) -> Option<Self> {
+		Self::deserialize_runs(data.chunks_exact(3).map(|run| {
+			(u16::from_le_bytes([run[0], run[1]]), run[2] as Voxel)
+		}))
+	}
+
+	fn deserialize_runs(runs: impl Iterator<Item = (u16, Voxel)>) -> Option<Self> {
+		let mut new_core = Self::new();
+		new_core.modified = true;
+		let mut i = 0;
+		for (run_len, voxel) in runs {
+			let run_len = run_len as usize;
+			if i + run_len > VOLUME {
+				return None;
+			}
+			if run_len == VOLUME {
+				new_core.voxels = Storage::Uniform(voxel);
+			}
+			else {
+				for index in i..(i + run_len) {
+					new_core.voxels.set(index, voxel);
+				}
+			}
+			if voxel != EMPTY {
+				new_core.empty = false;
+			}
+			i += run_len;
+		}
+		if i != VOLUME {
+			return None;
+		}
+		new_core.compact();
+		Some(new_core)
+	}
+}
+
+impl Default for ChunkCore {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+
+impl Neighbours {
+	pub fn set_face(&mut self, face: usize, border: Border) {
+		self.faces[face] = border;
+	}
+
+	pub fn set_diagonal(&mut self, diagonal: usize, mask: u32) {
+		self.diagonals[diagonal] = mask;
+	}
+}
+
+
+impl Border {
+	#[inline]
+	fn is_opaque(&self, row: usize, bit: usize) -> bool {
+		(self.opaque[row] >> bit) & 1 == 1
+	}
+
+	/// the voxel type at a position, if it is known
+	#[inline]
+	fn voxel(&self, row: usize, bit: usize) -> Option<Voxel> {
+		self.transparent.as_ref().map(|voxels| voxels[row * WIDTH + bit])
+	}
+}
+
+
+impl Storage {
+	#[inline]
+	fn get(&self, i: usize) -> Voxel {
+		match self {
+			Self::Uniform(voxel) => *voxel,
+			Self::Paletted(paletted) => paletted.get(i),
+		}
+	}
+
+	#[inline]
+	fn set(&mut self, i: usize, voxel: Voxel) {
+		match self {
+			Self::Uniform(current) => {
+				if *current != voxel {
+					let mut paletted = PalettedVoxels::new(vec![*current], 1);
+					paletted.set(i, voxel);
+					*self = Self::Paletted(paletted);
+				}
+			}
+			Self::Paletted(paletted) => paletted.set(i, voxel),
+		}
+	}
+}
+
+
+impl PalettedVoxels {
+	fn new(palette: Vec<Voxel>, bits: usize) -> Self {
+		Self {
+			palette,
+			bits,
+			data: vec![0; VOLUME * bits / 64].into_boxed_slice(),
+		}
+	}
+
+	#[inline]
+	fn get(&self, i: usize) -> Voxel {
+		self.palette[self.index(i)]
+	}
+
+	#[inline]
+	fn set(&mut self, i: usize, voxel: Voxel) {
+		let index = match self.palette.iter().position(|&v| v == voxel) {
+			Some(index) => index,
+			None => {
+				if self.palette.len() == 1 << self.bits {
+					self.repack(self.bits * 2);
+				}
+				self.palette.push(voxel);
+				self.palette.len() - 1
+			}
+		};
+		self.set_index(i, index);
+	}
+
+	/// palette index of voxel i
+	/// bits is always a power of two, so an index never spans two words
+	#[inline]
+	fn index(&self, i: usize) -> usize {
+		let bit = i * self.bits;
+		let mask = (1 << self.bits) - 1;
+		((self.data[bit / 64] >> (bit % 64)) & mask) as usize
+	}
+
+	#[inline]
+	fn set_index(&mut self, i: usize, index: usize) {
+		let bit = i * self.bits;
+		let mask: u64 = (1 << self.bits) - 1;
+		let word = &mut self.data[bit / 64];
+		*word = (*word & !(mask << (bit % 64))) | ((index as u64) << (bit % 64));
+	}
+
+	/// see ChunkCore::for_each_palette_index, BITS must be self.bits
+	#[inline]
+	fn for_each_index<const BITS: usize>(&self, mut f: impl FnMut(usize, usize)) {
+		let mask = (1 << BITS) - 1;
+		for (w, &word) in self.data.iter().enumerate() {
+			for j in 0..64 / BITS {
+				f(w * (64 / BITS) + j, ((word >> (j * BITS)) & mask) as usize);
+			}
+		}
+	}
+
+	fn repack(&mut self, bits: usize) {
+		let mut repacked = Self::new(std::mem::take(&mut self.palette), bits);
+		for i in 0..VOLUME {
+			repacked.set_index(i, self.index(i));
+		}
+		*self = repacked;
+	}
+
+	/// returns a smaller representation if one exists
+	fn compacted(&self) -> Option<Storage> {
+		let mut used = vec![false; self.palette.len()];
+		for i in 0..VOLUME {
+			used[self.index(i)] = true;
+		}
+		let palette: Vec<Voxel> = self.palette.iter()
+			.zip(used)
+			.filter(|(_, used)| *used)
+			.map(|(v, _)| *v)
+			.collect();
+
+		if palette.len() == 1 {
+			return Some(Storage::Uniform(palette[0]));
+		}
+		let bits = bits_for(palette.len());
+		if palette.len() == self.palette.len() && bits == self.bits {
+			return None;
+		}
+		let mut compacted = Self::new(palette, bits);
+		for i in 0..VOLUME {
+			compacted.set(i, self.get(i));
+		}
+		Some(Storage::Paletted(compacted))
+	}
+}
+
+/// face, row and bit of a position just outside the chunk in one of the neighbouring chunks
+/// None if it is inside the chunk, or outside on more than one axis
+fn border_pos(vpos: VoxelPos) -> Option<(usize, usize, usize)> {
+	let (x, y, z) = vpos;
+	let inside = |v: i8| (0..WIDTH_I8).contains(&v);
+	let (face, row, bit) = if inside(y) && inside(z) {
+		match x { WIDTH_I8 => (0, y, z), -1 => (1, y, z), _ => return None }
+	} else if inside(x) && inside(z) {
+		match y { WIDTH_I8 => (2, x, z), -1 => (3, x, z), _ => return None }
+	} else if inside(x) && inside(y) {
+		match z { WIDTH_I8 => (4, x, y), -1 => (5, x, y), _ => return None }
+	} else {
+		return None;
+	};
+	Some((face, row as usize, bit as usize))
+}
+
+/// diagonal neighbour and bit of a position just outside the chunk on two or three axes
+/// None if it is inside the chunk, outside on one axis, or further out
+fn diagonal_pos(vpos: VoxelPos) -> Option<(usize, usize)> {
+	let side = |v: i8| match v {
+		WIDTH_I8 => Some(1),
+		-1 => Some(-1),
+		v if (0..WIDTH_I8).contains(&v) => Some(0),
+		_ => None,
+	};
+	let offset = (side(vpos.0)?, side(vpos.1)?, side(vpos.2)?);
+	let diagonal = DIAGONALS.iter().position(|&o| o == offset)?;
+	let bit = if offset.0 == 0 {
+		vpos.0
+	} else if offset.1 == 0 {
+		vpos.1
+	} else if offset.2 == 0 {
+		vpos.2
+	} else {
+		0
+	};
+	Some((diagonal, bit as usize))
+}
+
+/// smallest power of two bit count that can index a palette of this length
+fn bits_for(palette_len: usize) -> usize {
+	let mut bits = 1;
+	while 1 << bits < palette_len {
+		bits *= 2;
+	}
+	bits
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::types::test_types::{self, GLASS};
+
+	#[test]
+	fn new_chunk_is_empty() {
+		let core = ChunkCore::new();
+		assert!(core.empty);
+		assert_eq!(core.get_voxel(ivec3(3, 4, 5)), EMPTY);
+		assert_eq!(core.get_voxel(ivec3(-1, 0, 0)), EMPTY);
+	}
+
+	#[test]
+	fn palette_grows_and_keeps_voxels() {
+		let mut core = ChunkCore::new();
+		for i in 0..VOLUME {
+			core.set_voxel(index_to_vposv(i), (i % 300) as Voxel);
+		}
+		for i in 0..VOLUME {
+			assert_eq!(core.get_voxel_index(i), (i % 300) as Voxel);
+		}
+	}
+
+	#[test]
+	fn compact_returns_to_uniform() {
+		let mut core = ChunkCore::new_filled(1);
+		core.set_voxel(ivec3(1, 2, 3), 2);
+		assert!(matches!(core.voxels, Storage::Paletted(_)));
+		core.set_voxel(ivec3(1, 2, 3), 1);
+		core.compact();
+		assert!(matches!(core.voxels, Storage::Uniform(1)));
+	}
+
+	#[test]
+	fn serialize_round_trip() {
+		let mut core = ChunkCore::new();
+		for i in (0..VOLUME).step_by(7) {
+			core.set_voxel(index_to_vposv(i), (i % 5) as Voxel);
+		}
+		let loaded = ChunkCore::deserialize(&core.serialize()).unwrap();
+		assert!(loaded.modified);
+		for i in 0..VOLUME {
+			assert_eq!(loaded.get_voxel_index(i), core.get_voxel_index(i));
+		}
+	}
+
+	#[test]
+	fn deserialize_u8_runs() {
+		// 100 voxels of type 3, then air
+		let mut data = Vec::new();
+		data.extend_from_slice(&100u16.to_le_bytes());
+		data.push(3);
+		data.extend_from_slice(&((VOLUME - 100) as u16).to_le_bytes());
+		data.push(0);
+		let loaded = ChunkCore::deserialize_u8(&data).unwrap();
+		assert_eq!(loaded.get_voxel_index(99), 3);
+		assert_eq!(loaded.get_voxel_index(100), EMPTY);
+		assert!(!loaded.empty);
+		assert!(ChunkCore::deserialize_u8(&data[..3]).is_none());
+	}
+
+	#[test]
+	fn deserialize_rejects_wrong_length() {
+		let mut data = ChunkCore::new_filled(1).serialize();
+		assert!(ChunkCore::deserialize(&data).is_some());
+		data.extend_from_slice(&1u16.to_le_bytes());
+		data.extend_from_slice(&2u16.to_le_bytes());
+		assert!(ChunkCore::deserialize(&data).is_none());
+		assert!(ChunkCore::deserialize(&[]).is_none());
+	}
+
+	#[test]
+	fn neighbour_borders_and_diagonals() {
+		let mut core = ChunkCore::new();
+		let mut other = ChunkCore::new();
+		other.set_voxel(ivec3(0, 3, 4), 1);
+		// other is on the +x side of core
+		core.set_neighbour(0, other.border(1));
+		assert!(core.is_opaque_at((WIDTH_I8, 3, 4)));
+		assert!(!core.is_opaque_at((WIDTH_I8, 4, 4)));
+		assert!(!core.set_neighbour(0, other.border(1)));
+
+		let mut corner = ChunkCore::new();
+		corner.set_voxel(ivec3(0, 0, 0), 1);
+		let diagonal = DIAGONALS.iter().position(|&o| o == (1, 1, 1)).unwrap();
+		core.set_diagonal(diagonal, corner.diagonal_opacity(DIAGONALS[opposite_diagonal(diagonal)]));
+		assert!(core.is_opaque_at((WIDTH_I8, WIDTH_I8, WIDTH_I8)));
+	}
+
+	#[test]
+	fn sets_all_neighbours_at_once() {
+		let mut other = ChunkCore::new();
+		other.set_voxel(ivec3(0, 3, 4), 1);
+		let mut neighbours = Neighbours::default();
+		neighbours.set_face(0, other.border(1));
+		neighbours.set_diagonal(0, 1);
+
+		let mut core = ChunkCore::new();
+		assert!(core.set_neighbours(neighbours.clone()));
+		assert!(core.is_opaque_at((WIDTH_I8, 3, 4)));
+		assert!(!core.set_neighbours(neighbours));
+		assert!(!core.set_neighbour(0, other.border(1)));
+		assert!(!core.set_diagonal(0, 1));
+	}
+
+	#[test]
+	fn transparent_voxels_hide_their_own_type() {
+		test_types::init();
+		let mut core = ChunkCore::new();
+		core.set_voxel(ivec3(1, 1, 1), GLASS);
+		core.set_voxel(ivec3(2, 1, 1), 1);
+		assert!(core.hides_face(GLASS, (1, 1, 1)));
+		assert!(!core.hides_face(1, (1, 1, 1)));
+		assert!(core.hides_face(GLASS, (2, 1, 1)));
+		assert!(!core.hides_face(EMPTY, (0, 0, 0)));
+	}
+}
@@ -0,0 +1,102 @@
+use crate::chunk::ChunkCore;
+use crate::common::*;
+use crate::math::Vec3;
+use crate::mesh::{BitGrid, QUAD_OFFSETS, greedy_quads};
+
+
+/// triangles around the solid voxels of a chunk, 3 vertices each, for a concave collision shape
+/// solid cubes are merged into rectangles like in the greedy mesher, other solid shapes use their whole model
+/// faces on the border of the chunk are always kept, so edits never change the collision of the neighbouring chunks
+pub fn collision_faces(core: &ChunkCore) -> Vec<Vec3> {
+	let mut faces = Vec::new();
+	let solid = BitGrid::new(core, |v| v.is_solid() && v.is_cube());
+
+	for face in 0..6usize {
+		for layer in 0..WIDTH {
+			let top_layer = if face.is_multiple_of(2) { layer + 1 } else { layer.wrapping_sub(1) };
+			// faces against another solid cube are never touched, so quads may be stretched over them
+			let covered: [u32; WIDTH] = std::array::from_fn(|slice| {
+				if top_layer < WIDTH { solid.row(face, top_layer, slice) } else { 0 }
+			});
+			let visible: [u32; WIDTH] = std::array::from_fn(|slice| solid.row(face, layer, slice) & !covered[slice]);
+			greedy_quads(visible, &covered, |quad| {
+				let corners = quad.transformed_verts(face, layer);
+				faces.extend(QUAD_OFFSETS.map(|i| corners[i]));
+			});
+		}
+	}
+
+	for v_index in 0..VOLUME {
+		let voxel = core.get_voxel_index(v_index);
+		if !voxel.is_solid() {
+			continue;
+		}
+		if let Some(model) = voxel.model() {
+			let pos = index_to_vposv(v_index);
+			for quad in model {
+				faces.extend(quad.verts.map(|v| pos + v));
+			}
+		}
+	}
+	faces
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::types::test_types::{self, PLANT, SLAB};
+
+	fn quads(faces: &[Vec3]) -> usize {
+		assert_eq!(faces.len() % 6, 0);
+		faces.len() / 6
+	}
+
+	#[test]
+	fn empty_chunk_has_no_collision() {
+		assert!(collision_faces(&ChunkCore::new()).is_empty());
+	}
+
+	#[test]
+	fn full_chunk_is_one_quad_per_side() {
+		let faces = collision_faces(&ChunkCore::new_filled(1));
+		assert_eq!(quads(&faces), 6);
+		assert!(faces.iter().all(|v| [v.x, v.y, v.z].iter().any(|&c| c == 0.0 || c == WIDTH_F)));
+	}
+
+	#[test]
+	fn touching_cubes_are_merged() {
+		let mut core = ChunkCore::new();
+		core.set_voxel(ivec3(3, 3, 3), 1);
+		core.set_voxel(ivec3(4, 3, 3), 2);
+		// solidity is all that matters, so different types merge too
+		assert_eq!(quads(&collision_faces(&core)), 6);
+	}
+
+	#[test]
+	fn border_faces_are_kept() {
+		let mut core = ChunkCore::new();
+		core.set_voxel(ivec3(31, 0, 0), 1);
+		let faces = collision_faces(&core);
+		assert_eq!(quads(&faces), 6);
+		assert!(faces.iter().any(|v| v.x == WIDTH_F));
+		assert!(faces.iter().any(|v| v.y == 0.0));
+	}
+
+	#[test]
+	fn models_use_their_shape() {
+		test_types::init();
+		let mut core = ChunkCore::new();
+		core.set_voxel(ivec3(5, 5, 5), PLANT);
+		assert!(collision_faces(&core).is_empty());
+
+		core.set_voxel(ivec3(5, 5, 5), SLAB);
+		let faces = collision_faces(&core);
+		assert_eq!(quads(&faces), 6);
+		assert!(faces.iter().all(|v| v.y <= 5.5));
+
+		// the top half of the cube's side is open, so the face next to the slab stays
+		core.set_voxel(ivec3(6, 5, 5), 1);
+		assert_eq!(quads(&collision_faces(&core)), 12);
+	}
+}
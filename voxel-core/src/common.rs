@@ -0,0 +1,250 @@
+use crate::math::Vec3;
+use crate::shapes::{ModelQuad, Shape};
+use crate::types::voxel_types;
+
+pub const WIDTH: usize = 32;
+pub const AREA: usize = WIDTH * WIDTH;
+pub const VOLUME: usize = AREA * WIDTH;
+
+pub const WIDTH_F: f32 = WIDTH as f32;
+pub const WIDTH_I8: i8 = WIDTH as i8;
+
+/// chunks store voxels in per-chunk palettes, so typical chunks only use a few bits per voxel
+pub type Voxel = u16;
+pub const EMPTY: Voxel = 0;
+
+pub const DEBUG_UVS: bool = true;
+
+/// Represents a chunk location
+/// Loc(1,2,3) correspsonds to the chunk at (32, 64, 96) assuming a chunk size of 32
+pub type ChunkLoc = (i32, i32, i32);
+pub type VoxelPos = (i8, i8, i8);
+
+/// offsets to the neighbouring chunks, in the same order as the mesh faces
+pub const NEIGHBOURS: [ChunkLoc; 6] = [
+	(1, 0, 0), (-1, 0, 0),
+	(0, 1, 0), (0, -1, 0),
+	(0, 0, 1), (0, 0, -1)];
+
+/// offsets to the chunks that only touch along an edge or at a corner
+/// listed in pairs of opposites, like the faces
+pub const DIAGONALS: [ChunkLoc; 20] = [
+	(1, 1, 0), (-1, -1, 0),
+	(1, -1, 0), (-1, 1, 0),
+	(1, 0, 1), (-1, 0, -1),
+	(1, 0, -1), (-1, 0, 1),
+	(0, 1, 1), (0, -1, -1),
+	(0, 1, -1), (0, -1, 1),
+	(1, 1, 1), (-1, -1, -1),
+	(1, 1, -1), (-1, -1, 1),
+	(1, -1, 1), (-1, 1, -1),
+	(1, -1, -1), (-1, 1, 1)];
+
+#[inline]
+pub const fn opposite_face(face: usize) -> usize {
+	face ^ 1
+}
+
+#[inline]
+pub const fn opposite_diagonal(diagonal: usize) -> usize {
+	diagonal ^ 1
+}
+
+#[inline]
+pub fn loc_add(a: ChunkLoc, b: ChunkLoc) -> ChunkLoc {
+	(a.0 + b.0, a.1 + b.1, a.2 + b.2)
+}
+
+/// Convert world coordinate to a chunk location
+#[inline]
+pub fn wpos_to_locv(world_pos: Vec3) -> Vec3 {
+	(world_pos / WIDTH_F).floor()
+}
+
+#[inline]
+pub fn locv_to_wpos(locv: Vec3) -> Vec3 {
+	locv * WIDTH_F
+}
+
+#[inline]
+pub fn wpos_to_loc(world_pos: Vec3) -> ChunkLoc {
+	locv_to_loc(wpos_to_locv(world_pos))
+}
+
+/// Convert Vec3 to i32 tuple
+#[inline]
+pub fn locv_to_loc(loc: Vec3) -> ChunkLoc {
+	(loc.x as i32, loc.y as i32, loc.z as i32)
+}
+
+#[inline]
+pub fn loc_to_wpos(loc: ChunkLoc) -> Vec3 {
+	Vec3::new(loc.0 as f32, loc.1 as f32, loc.2 as f32) * WIDTH_F
+}
+
+#[inline]
+pub fn loc_to_locv(loc: ChunkLoc) -> Vec3 {
+	Vec3::new(loc.0 as f32, loc.1 as f32, loc.2 as f32)
+}
+
+/// convert world coordinate to a position within the chunk
+#[inline]
+pub fn wpos_to_vposv(world_pos: Vec3) -> Vec3 {
+	world_pos.floor().posmod(WIDTH_F)
+}
+
+/// [i32] to [Vec3]
+#[inline]
+pub const fn ivec3(x: i32, y: i32, z: i32) -> Vec3 {
+	Vec3::new(x as f32, y as f32, z as f32)
+}
+
+#[inline]
+pub const fn uvec3(x: usize, y: usize, z: usize) -> Vec3 {
+	Vec3::new(x as f32, y as f32, z as f32)
+}
+
+#[inline]
+pub fn vposv_in_bounds(pos: Vec3) -> bool{
+	pos.x >= 0.0 && pos.x < WIDTH_F &&
+	pos.y >= 0.0 && pos.y < WIDTH_F &&
+	pos.z >= 0.0 && pos.z < WIDTH_F
+}
+
+#[inline]
+pub fn vpos_in_bounds(vpos: VoxelPos) -> bool{
+	vpos.0 >= 0 && vpos.0 < WIDTH_I8 &&
+	vpos.1 >= 0 && vpos.1 < WIDTH_I8 &&
+	vpos.2 >= 0 && vpos.2 < WIDTH_I8
+}
+
+/// faces of the chunk that a local position lies on
+pub fn border_faces(vposv: Vec3) -> Vec<usize> {
+	let max = WIDTH_F - 1.0;
+	let mut faces = Vec::new();
+	if vposv.x == max { faces.push(0); }
+	if vposv.x == 0.0 { faces.push(1); }
+	if vposv.y == max { faces.push(2); }
+	if vposv.y == 0.0 { faces.push(3); }
+	if vposv.z == max { faces.push(4); }
+	if vposv.z == 0.0 { faces.push(5); }
+	faces
+}
+
+/// diagonal neighbours of the chunk that a local position touches, as indices into DIAGONALS
+pub fn border_diagonals(vposv: Vec3) -> Vec<usize> {
+	let max = WIDTH_F - 1.0;
+	let side = |v: f32| if v == max { 1 } else if v == 0.0 { -1 } else { 0 };
+	let (x, y, z) = (side(vposv.x), side(vposv.y), side(vposv.z));
+	DIAGONALS.iter()
+		.enumerate()
+		.filter(|(_, o)| (o.0 == 0 || o.0 == x) && (o.1 == 0 || o.1 == y) && (o.2 == 0 || o.2 == z))
+		.map(|(i, _)| i)
+		.collect()
+}
+
+#[inline]
+pub fn vposv_to_vpos(vposv: Vec3) -> VoxelPos {
+	(vposv.x as i8, vposv.y as i8, vposv.z as i8)
+}
+
+#[inline]
+pub fn vpos_to_index(vpos: VoxelPos) -> usize {
+	vpos.0 as usize * AREA
+	+ vpos.1 as usize * WIDTH
+	+ vpos.2 as usize
+}
+
+#[inline]
+pub fn vposv_to_index(vposv: Vec3) -> usize {
+	vposv.x as usize * AREA
+	+ vposv.y as usize * WIDTH
+	+ vposv.z as usize
+}
+
+#[inline]
+pub fn index_to_vpos(i: usize) -> VoxelPos {
+	(
+		(i/AREA) as i8,
+		(i/WIDTH % WIDTH) as i8,
+		(i % WIDTH) as i8
+	)
+}
+
+#[inline]
+pub fn index_to_vposv(i: usize) -> Vec3 {
+	Vec3::new(
+		((i / AREA) as f32).floor(),
+		((i/WIDTH % WIDTH) as f32).floor(),
+		(i % WIDTH) as f32
+	)
+}
+
+pub trait VoxelData {
+	fn name(&self) -> String;
+	fn is_air(&self) -> bool;
+	fn is_surface(&self) -> bool;
+	fn is_transparent(&self) -> bool;
+	fn is_opaque(&self) -> bool;
+	fn is_solid(&self) -> bool;
+	fn is_selectable(&self) -> bool;
+	fn texture_layer(&self, face: usize) -> u16;
+	fn is_cube(&self) -> bool;
+	fn model(&self) -> Option<&'static [ModelQuad]>;
+}
+
+/// properties are looked up in voxel_types(); unregistered types are opaque and solid cubes
+/// only cubes can be opaque, other shapes never hide their neighbours
+impl VoxelData for Voxel {
+	fn name(&self) -> String {
+		match voxel_types().get(*self) {
+			Some(t) => t.name.clone(),
+			None => format!("{}", self),
+		}
+	}
+	
+	#[inline]
+	fn is_air(&self) -> bool {
+		*self == 0
+	}
+
+	#[inline]
+	fn is_surface(&self) -> bool {
+		*self != 0
+	}
+
+	#[inline]
+	fn is_transparent(&self) -> bool {
+		voxel_types().get(*self).is_some_and(|t| t.transparent)
+	}
+
+	#[inline]
+	fn is_opaque(&self) -> bool {
+		!self.is_transparent() && self.is_cube()
+	}
+
+	#[inline]
+	fn is_solid(&self) -> bool {
+		voxel_types().get(*self).is_none_or(|t| t.solid)
+	}
+
+	#[inline]
+	fn is_selectable(&self) -> bool {
+		voxel_types().get(*self).is_none_or(|t| t.selectable)
+	}
+
+	#[inline]
+	fn texture_layer(&self, face: usize) -> u16 {
+		voxel_types().get(*self).map_or(0, |t| t.layers[face])
+	}
+
+	#[inline]
+	fn is_cube(&self) -> bool {
+		voxel_types().get(*self).is_none_or(|t| t.shape == Shape::Cube)
+	}
+
+	#[inline]
+	fn model(&self) -> Option<&'static [ModelQuad]> {
+		voxel_types().get(*self)?.model.as_deref()
+	}
+}
@@ -0,0 +1,15 @@
+//! Voxel storage, meshing, collision, movement, terrain generation and raycasting, without any Godot types.
+//! gdvoxels wraps this for use from Godot; everything here can be tested with plain `cargo test`.
+
+pub mod common;
+pub mod chunk;
+pub mod collision;
+pub mod math;
+pub mod mesh;
+pub mod movement;
+pub mod raycast;
+pub mod shapes;
+pub mod snapshot;
+pub mod storage;
+pub mod terrain;
+pub mod types;
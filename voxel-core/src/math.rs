@@ -0,0 +1,227 @@
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+
+/// a 3d vector, laid out like Godot's Vector3
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Vec3 {
+	pub x: f32,
+	pub y: f32,
+	pub z: f32,
+}
+
+/// a 2d vector, laid out like Godot's Vector2
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Vec2 {
+	pub x: f32,
+	pub y: f32,
+}
+
+/// an axis aligned box, laid out like Godot's AABB
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Aabb {
+	pub pos: Vec3,
+	pub size: Vec3,
+}
+
+
+impl Vec3 {
+	pub const ZERO: Self = Self::new(0.0, 0.0, 0.0);
+	pub const ONE: Self = Self::new(1.0, 1.0, 1.0);
+	pub const UP: Self = Self::new(0.0, 1.0, 0.0);
+
+	#[inline]
+	pub const fn new(x: f32, y: f32, z: f32) -> Self {
+		Self { x, y, z }
+	}
+
+	#[inline]
+	pub fn floor(self) -> Self {
+		Self::new(self.x.floor(), self.y.floor(), self.z.floor())
+	}
+
+	/// componentwise modulo that is never negative for a positive `m`
+	#[inline]
+	pub fn posmod(self, m: f32) -> Self {
+		Self::new(self.x.rem_euclid(m), self.y.rem_euclid(m), self.z.rem_euclid(m))
+	}
+
+	#[inline]
+	pub fn abs(self) -> Self {
+		Self::new(self.x.abs(), self.y.abs(), self.z.abs())
+	}
+
+	/// -1, 0 or 1 for each component
+	#[inline]
+	pub fn sign(self) -> Self {
+		let sign = |v: f32| if v > 0.0 { 1.0 } else if v < 0.0 { -1.0 } else { 0.0 };
+		Self::new(sign(self.x), sign(self.y), sign(self.z))
+	}
+
+	#[inline]
+	pub fn dot(self, other: Self) -> f32 {
+		self.x * other.x + self.y * other.y + self.z * other.z
+	}
+
+	#[inline]
+	pub fn cross(self, other: Self) -> Self {
+		Self::new(
+			self.y * other.z - self.z * other.y,
+			self.z * other.x - self.x * other.z,
+			self.x * other.y - self.y * other.x,
+		)
+	}
+
+	#[inline]
+	pub fn length_squared(self) -> f32 {
+		self.dot(self)
+	}
+
+	#[inline]
+	pub fn length(self) -> f32 {
+		self.length_squared().sqrt()
+	}
+
+	#[inline]
+	pub fn normalized(self) -> Self {
+		self / self.length()
+	}
+
+	#[inline]
+	pub fn distance_squared_to(self, other: Self) -> f32 {
+		(other - self).length_squared()
+	}
+
+	/// index of the largest component, 0 for x, 1 for y and 2 for z
+	#[inline]
+	pub fn max_axis(self) -> usize {
+		if self.x >= self.y && self.x >= self.z {
+			0
+		} else if self.y >= self.z {
+			1
+		} else {
+			2
+		}
+	}
+
+	/// component by index, 0 for x, 1 for y and 2 for z
+	#[inline]
+	pub fn get(self, axis: usize) -> f32 {
+		match axis {
+			0 => self.x,
+			1 => self.y,
+			_ => self.z,
+		}
+	}
+
+	/// a copy with the component at axis replaced
+	#[inline]
+	pub fn with(self, axis: usize, value: f32) -> Self {
+		match axis {
+			0 => Self::new(value, self.y, self.z),
+			1 => Self::new(self.x, value, self.z),
+			_ => Self::new(self.x, self.y, value),
+		}
+	}
+
+	/// unit vector along an axis
+	#[inline]
+	pub fn axis(axis: usize) -> Self {
+		match axis {
+			0 => Self::new(1.0, 0.0, 0.0),
+			1 => Self::new(0.0, 1.0, 0.0),
+			_ => Self::new(0.0, 0.0, 1.0),
+		}
+	}
+}
+
+impl Aabb {
+	#[inline]
+	pub const fn new(pos: Vec3, size: Vec3) -> Self {
+		Self { pos, size }
+	}
+
+	#[inline]
+	pub fn end(self) -> Vec3 {
+		self.pos + self.size
+	}
+}
+
+impl Vec2 {
+	pub const ZERO: Self = Self::new(0.0, 0.0);
+
+	#[inline]
+	pub const fn new(x: f32, y: f32) -> Self {
+		Self { x, y }
+	}
+}
+
+
+impl Add for Vec3 {
+	type Output = Self;
+	#[inline]
+	fn add(self, other: Self) -> Self {
+		Self::new(self.x + other.x, self.y + other.y, self.z + other.z)
+	}
+}
+
+impl Sub for Vec3 {
+	type Output = Self;
+	#[inline]
+	fn sub(self, other: Self) -> Self {
+		Self::new(self.x - other.x, self.y - other.y, self.z - other.z)
+	}
+}
+
+impl Mul for Vec3 {
+	type Output = Self;
+	#[inline]
+	fn mul(self, other: Self) -> Self {
+		Self::new(self.x * other.x, self.y * other.y, self.z * other.z)
+	}
+}
+
+impl Mul<f32> for Vec3 {
+	type Output = Self;
+	#[inline]
+	fn mul(self, s: f32) -> Self {
+		Self::new(self.x * s, self.y * s, self.z * s)
+	}
+}
+
+impl Div for Vec3 {
+	type Output = Self;
+	#[inline]
+	fn div(self, other: Self) -> Self {
+		Self::new(self.x / other.x, self.y / other.y, self.z / other.z)
+	}
+}
+
+impl Div<f32> for Vec3 {
+	type Output = Self;
+	#[inline]
+	fn div(self, s: f32) -> Self {
+		Self::new(self.x / s, self.y / s, self.z / s)
+	}
+}
+
+impl Neg for Vec3 {
+	type Output = Self;
+	#[inline]
+	fn neg(self) -> Self {
+		Self::new(-self.x, -self.y, -self.z)
+	}
+}
+
+impl AddAssign for Vec3 {
+	#[inline]
+	fn add_assign(&mut self, other: Self) {
+		*self = *self + other;
+	}
+}
+
+impl SubAssign for Vec3 {
+	#[inline]
+	fn sub_assign(&mut self, other: Self) {
+		*self = *self - other;
+	}
+}
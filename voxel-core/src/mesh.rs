@@ -0,0 +1,1317 @@
+use std::sync::OnceLock;
+
+use crate::chunk::ChunkCore;
+use crate::common::*;
+use crate::math::{Vec2, Vec3};
+use crate::shapes::ModelQuad;
+
+pub const NORMALS: [Vec3; 6] = [
+	ivec3(1, 0, 0), ivec3(-1, 0, 0),
+	ivec3(0, 1, 0), ivec3(0, -1, 0),
+	ivec3(0, 0, 1), ivec3(0, 0, -1)];
+
+const NORMALS_I8: [VoxelPos; 6] = [
+	(1, 0, 0), (-1, 0, 0),
+	(0, 1, 0), (0, -1, 0),
+	(0, 0, 1), (0, 0, -1)];
+
+pub const FACE_VERTS: [[Vec3; 4]; 6] = [
+	[ivec3(1, 1, 1), ivec3(1, 1, 0), ivec3(1, 0, 0), ivec3(1, 0, 1)],
+	[ivec3(0, 1, 0), ivec3(0, 1, 1), ivec3(0, 0, 1), ivec3(0, 0, 0)],
+	[ivec3(0, 1, 0), ivec3(1, 1, 0), ivec3(1, 1, 1), ivec3(0, 1, 1)],
+	[ivec3(0, 0, 1), ivec3(1, 0, 1), ivec3(1, 0, 0), ivec3(0, 0, 0)],
+	[ivec3(0, 1, 1), ivec3(1, 1, 1), ivec3(1, 0, 1), ivec3(0, 0, 1)],
+	[ivec3(1, 1, 0), ivec3(0, 1, 0), ivec3(0, 0, 0), ivec3(1, 0, 0)]];
+
+pub const QUAD_OFFSETS: [usize; 6] = [0, 1, 2, 2, 3, 0];
+/// same quad split along the other diagonal
+const QUAD_OFFSETS_FLIPPED: [usize; 6] = [1, 2, 3, 3, 0, 1];
+
+/// tangent of each face, pointing along the u axis of its uvs
+/// w is the sign Godot uses to get the binormal from cross(normal, tangent)
+const FACE_TANGENTS: [[f32; 4]; 6] = [
+	[0.0, 0.0, -1.0, -1.0], [0.0, 0.0, 1.0, -1.0],
+	[1.0, 0.0, 0.0, -1.0], [-1.0, 0.0, 0.0, -1.0],
+	[1.0, 0.0, 0.0, -1.0], [-1.0, 0.0, 0.0, -1.0]];
+
+/// debug uv2 for each corner of a quad, spanning the whole quad
+const DEBUG_UVS_CORNERS: [Vec2; 4] = [
+	Vec2::new(1.0, 0.0), Vec2::new(0.0, 0.0),
+	Vec2::new(0.0, 1.0), Vec2::new(1.0, 1.0)];
+
+/// vertex brightness for each ambient occlusion level, from fully occluded to unoccluded
+const AO_CURVE: [f32; 4] = [0.4, 0.6, 0.8, 1.0];
+
+/// what a surface holds, which decides its material
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SurfaceKind {
+	/// one voxel type, using the material from the registry
+	Voxel(Voxel),
+	/// every opaque voxel type, using the texture array
+	Opaque,
+	/// every transparent voxel type, using the texture array with alpha
+	Transparent,
+}
+
+
+/// builds the mesh of a chunk into plain buffers, one surface per material
+pub struct Mesher {
+	inited: bool,
+	/// put all opaque voxel types in one surface, and all transparent ones in another
+	texture_array: bool,
+	surfaces: Vec<Surface>,
+}
+
+/// triangle lists of one surface, 6 vertices per quad
+pub struct Surface {
+	kind: SurfaceKind,
+	vertexes: Vec<Vec3>,
+	normals: Vec<Vec3>,
+	uvs: Vec<Vec2>,
+	/// only used when DEBUG_UVS is set
+	debug_uvs: Vec<Vec2>,
+	/// 4 floats per vertex
+	tangents: Vec<f32>,
+	/// rgba
+	colors: Vec<[f32; 4]>,
+}
+
+
+impl Mesher {
+	pub fn new() -> Self {
+		Self {
+			inited: false,
+			texture_array: false,
+			surfaces: Vec::new(),
+		}
+	}
+
+	/// whether a mesh has been generated yet, the remesh functions need one to update
+	pub fn is_inited(&self) -> bool {
+		self.inited
+	}
+
+	/// the surfaces that have quads, opaque ones first, then the transparent pass
+	pub fn surfaces(&self) -> impl Iterator<Item = &Surface> + '_ {
+		[false, true].into_iter().flat_map(move |transparent| {
+			self.surfaces.iter().filter(move |s| s.quad_count() > 0 && s.is_transparent() == transparent)
+		})
+	}
+
+	/// fast but suboptimal mesh
+	pub fn generate_fast(&mut self, core: &ChunkCore, texture_array: bool) {
+		self.inited = true;
+		self.texture_array = texture_array;
+		for s in self.surfaces.iter_mut() {
+			s.clear();
+		}
+		for v_index in 0..VOLUME {
+			let voxel = core.get_voxel_index(v_index);
+			if voxel.is_surface() {
+				let surf_i = self.ensure_surface(voxel);
+				let pos = index_to_vposv(v_index);
+				self.add_voxel(pos, voxel, surf_i, core);
+			}
+		}
+	}
+
+	/// greedy mesh built from bitmasks, one u32 row per slice with a bit per offset
+	/// <5% triangle count on normal terrain
+	/// faces under opaque voxels are never seen, so quads are stretched over them to use fewer quads
+	pub fn generate_greedy(&mut self, core: &ChunkCore, texture_array: bool) {
+		self.inited = true;
+		self.texture_array = texture_array;
+		for s in self.surfaces.iter_mut() {
+			s.clear();
+		}
+		// properties are looked up once per palette entry instead of once per voxel
+		let palette = core.palette();
+		let opaque_entries: Vec<bool> = palette.iter().map(|v| v.is_opaque()).collect();
+		let cube_entries: Vec<bool> = palette.iter().map(|v| v.is_surface() && v.is_cube()).collect();
+		// transparent cubes hide faces of their own type, which the opaque grid does not cover
+		let see_through: Vec<bool> = palette.iter().map(|v| v.is_surface() && v.is_cube() && !v.is_opaque()).collect();
+		let model_entries: Vec<bool> = palette.iter().map(|v| v.model().is_some()).collect();
+		let [opaque, cubes] = BitGrid::from_palette(core, [&opaque_entries, &cube_entries]);
+
+		if !cube_entries.contains(&true) && !model_entries.contains(&true) {
+			return;
+		}
+		let ao_table = ao_table();
+
+		// visible faces, grouped by what they can be merged with: palette entry and ambient occlusion
+		let mut masks: Vec<(MaskKey, [u32; WIDTH])> = Vec::new();
+		// index + 1 into masks for every key, 0 for keys without faces in this layer
+		let mut mask_index = vec![0u32; palette.len() << 8];
+		for (face, &normal) in NORMALS_I8.iter().enumerate() {
+			for layer in 0..WIDTH {
+				let covered = covered_rows(core, &opaque, face, layer);
+				for &(key, _) in masks.iter() {
+					mask_index[key] = 0;
+				}
+				masks.clear();
+				// ambient occlusion only looks at the layer in front, which is what covers the faces
+				let front_opaque = |pos: VoxelPos| match slice_offset(face, pos) {
+					Some((slice, offset)) => (covered[slice] >> offset) & 1 == 1,
+					None => core.is_opaque_at(pos),
+				};
+				for slice in 0..WIDTH {
+					let mut visible = cubes.row(face, layer, slice) & !covered[slice];
+					while visible != 0 {
+						let offset = visible.trailing_zeros() as usize;
+						visible &= visible - 1;
+						let pos = layered_pos(face, layer, slice, offset);
+						let entry = core.palette_index(vpos_to_index(pos));
+						let voxel = palette[entry];
+						// faces between transparent voxels of the same type are culled,
+						// and other quads must not be stretched over them like under opaque voxels
+						if see_through[entry] && core.hides_face(voxel, (pos.0 + normal.0, pos.1 + normal.1, pos.2 + normal.2)) {
+							continue;
+						}
+						let ao = if (1..WIDTH - 1).contains(&slice) && (1..WIDTH - 1).contains(&offset) {
+							let around = |row: u32| (row >> (offset - 1)) & 0b111;
+							let bits = around(covered[slice - 1]) | around(covered[slice]) << 3 | around(covered[slice + 1]) << 6;
+							ao_table[face][bits as usize]
+						} else {
+							pack_ao(face_ao(pos, face, front_opaque))
+						};
+						let key = entry << 8 | ao as usize;
+						let i = match mask_index[key] {
+							0 => {
+								masks.push((key, [0; WIDTH]));
+								mask_index[key] = masks.len() as u32;
+								masks.len() - 1
+							}
+							i => i as usize - 1,
+						};
+						masks[i].1[slice] |= 1 << offset;
+					}
+				}
+
+				for &(key, mask) in masks.iter() {
+					let (voxel, ao) = (palette[key >> 8], unpack_ao(key as u8));
+					let surface = self.ensure_surface(voxel);
+					let surface = &mut self.surfaces[surface];
+					greedy_quads(mask, &covered, |q| surface.add_quad(q.transformed_verts(face, layer), face, voxel, ao));
+				}
+			}
+		}
+		// other shapes are added after the cubes
+		if model_entries.contains(&true) {
+			for v_index in 0..VOLUME {
+				let entry = core.palette_index(v_index);
+				if model_entries[entry] {
+					let voxel = palette[entry];
+					let surf_i = self.ensure_surface(voxel);
+					self.add_voxel(index_to_vposv(v_index), voxel, surf_i, core);
+				}
+			}
+		}
+	}
+
+	/// smooth surface around the opaque voxels, using surface nets
+	/// every cell between 8 voxel centers that is partly opaque gets one vertex, at the average of the crossings on its edges
+	/// cells on the border see one layer into the neighbouring chunks, so both chunks place the same vertices there
+	/// each quad belongs to the chunk of the opaque voxel it covers, whose type decides the material
+	/// other voxels are meshed like in the fast mesher
+	pub fn generate_smooth(&mut self, core: &ChunkCore, texture_array: bool) {
+		self.inited = true;
+		self.texture_array = texture_array;
+		for s in self.surfaces.iter_mut() {
+			s.clear();
+		}
+
+		// sample (x, y, z) is the voxel at (x - 1, y - 1, z - 1)
+		let mut opaque = vec![false; SAMPLES * SAMPLES * SAMPLES];
+		for x in 0..SAMPLES {
+			for y in 0..SAMPLES {
+				for z in 0..SAMPLES {
+					opaque[sample_index(x, y, z)] = core.is_opaque_at((x as i8 - 1, y as i8 - 1, z as i8 - 1));
+				}
+			}
+		}
+
+		// cell (x, y, z) lies between samples x..x+1, y..y+1 and z..z+1, centered on voxel corner (x, y, z)
+		let mut cells = vec![None; CELLS * CELLS * CELLS];
+		for x in 0..CELLS {
+			for y in 0..CELLS {
+				for z in 0..CELLS {
+					cells[cell_index(x, y, z)] = cell_vertex(&opaque, x, y, z);
+				}
+			}
+		}
+
+		for axis in 0..3 {
+			let (u, w) = ((axis + 1) % 3, (axis + 2) % 3);
+			for a in 0..CELLS {
+				for b in 1..SAMPLES - 1 {
+					for c in 1..SAMPLES - 1 {
+						let mut s = [0; 3];
+						s[axis] = a;
+						s[u] = b;
+						s[w] = c;
+						let mut next = s;
+						next[axis] += 1;
+						let (from, to) = (opaque[sample_index(s[0], s[1], s[2])], opaque[sample_index(next[0], next[1], next[2])]);
+						let (inside, face) = match (from, to) {
+							(true, false) => (s, axis * 2),
+							(false, true) => (next, axis * 2 + 1),
+							_ => continue,
+						};
+						if inside[axis] == 0 || inside[axis] == SAMPLES - 1 {
+							continue;
+						}
+						let mut corners = [Vec3::ZERO; 4];
+						let mut normals = [Vec3::ZERO; 4];
+						for (i, corner) in FACE_VERTS[face].iter().enumerate() {
+							let corner = [corner.x as usize, corner.y as usize, corner.z as usize];
+							let mut cell = s;
+							cell[u] = s[u] + corner[u] - 1;
+							cell[w] = s[w] + corner[w] - 1;
+							let (pos, normal) = cells[cell_index(cell[0], cell[1], cell[2])].unwrap();
+							corners[i] = pos;
+							normals[i] = normal;
+						}
+						let voxel = core.get_voxel_i(((inside[0] - 1) as i8, (inside[1] - 1) as i8, (inside[2] - 1) as i8));
+						let surf_i = self.ensure_surface(voxel);
+						self.surfaces[surf_i].add_smooth_quad(corners, normals, face, voxel);
+					}
+				}
+			}
+		}
+
+		for v_index in 0..VOLUME {
+			let voxel = core.get_voxel_index(v_index);
+			if voxel.is_surface() && !voxel.is_opaque() {
+				let surf_i = self.ensure_surface(voxel);
+				self.add_voxel(index_to_vposv(v_index), voxel, surf_i, core);
+			}
+		}
+	}
+
+	pub fn remesh_partial(&mut self, core: &ChunkCore, pos: Vec3, old_voxel: Voxel) {
+		self.inited = true;
+		let voxel = core.get_voxel_unsafe(pos);
+		if voxel == old_voxel {
+			return;
+		}
+
+		let mut adjacent_voxels = Vec::new();
+		let mut affected_surfaces = vec![self.ensure_surface(voxel), self.ensure_surface(old_voxel)];
+
+		for normal in NORMALS {
+			let v = core.get_voxel(pos - normal);
+			adjacent_voxels.push(v);
+			affected_surfaces.push(self.ensure_surface(v));
+		}
+
+		// remove the faces of the voxel and the faces of its neighbours that touch it
+		for surf_i in affected_surfaces.iter().filter(|&i| *i != usize::MAX) {
+			self.surfaces[*surf_i].remove_quads_in_bound(pos - Vec3::ONE * 0.1, pos + Vec3::ONE * 1.1);
+		}
+
+		if voxel != EMPTY {
+			let surf_i = affected_surfaces[0];
+			self.add_voxel(pos, voxel, surf_i, core);
+		}
+		if !voxel.is_opaque() { // set faces for surrounding voxels; essentially an inverted version of the other case
+			for face in 0..6 {
+				let other_voxel = adjacent_voxels[face];
+				if other_voxel.is_surface() {
+					let other_pos = pos - NORMALS[face];
+					let surf_i = affected_surfaces[face + 2];
+					self.add_voxel_face(other_pos, other_voxel, face, surf_i, core);
+				}
+			}
+		}
+	}
+
+	/// replace a single face of the voxel at pos
+	pub fn remesh_face(&mut self, core: &ChunkCore, pos: Vec3, face: usize) {
+		let voxel = core.get_voxel(pos);
+		if !voxel.is_surface() {
+			return;
+		}
+		let surf_i = self.ensure_surface(voxel);
+		let verts = [
+			pos + FACE_VERTS[face][0],
+			pos + FACE_VERTS[face][1],
+			pos + FACE_VERTS[face][2],
+			pos + FACE_VERTS[face][3],
+		];
+		let min = verts.iter().fold(verts[0], |a, b| Vec3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)));
+		let max = verts.iter().fold(verts[0], |a, b| Vec3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)));
+		self.surfaces[surf_i].remove_quads_in_bound(min - Vec3::ONE * 0.1, max + Vec3::ONE * 0.1);
+
+		self.add_voxel_face(pos, voxel, face, surf_i, core);
+	}
+
+	/// add the visible faces of a voxel; for models, the quads that are not on a side are always visible
+	#[inline]
+	fn add_voxel(&mut self, pos: Vec3, voxel: Voxel, surface_index: usize, core: &ChunkCore) {
+		if let Some(model) = voxel.model() {
+			for quad in model.iter().filter(|q| q.cull.is_none()) {
+				self.surfaces[surface_index].add_model_quad(pos, quad, voxel);
+			}
+		}
+		for face in 0..6 {
+			self.add_voxel_face(pos, voxel, face, surface_index, core);
+		}
+	}
+
+	/// add one face of a voxel if it is not hidden by the voxel it touches
+	/// for models this is every quad on that side
+	#[inline]
+	fn add_voxel_face(&mut self, pos: Vec3, voxel: Voxel, face: usize, surface_index: usize, core: &ChunkCore) {
+		if core.hides_face(voxel, vposv_to_vpos(pos + NORMALS[face])) {
+			return;
+		}
+		match voxel.model() {
+			Some(model) => {
+				for quad in model.iter().filter(|q| q.cull == Some(face)) {
+					self.surfaces[surface_index].add_model_quad(pos, quad, voxel);
+				}
+			}
+			None => {
+				let verts = [
+					pos + FACE_VERTS[face][0],
+					pos + FACE_VERTS[face][1],
+					pos + FACE_VERTS[face][2],
+					pos + FACE_VERTS[face][3],
+				];
+				let ao = face_ao(vposv_to_vpos(pos), face, |p| core.is_opaque_at(p));
+				self.surfaces[surface_index].add_quad(verts, face, voxel, ao);
+			}
+		}
+	}
+	
+	/// ensures a surface exists for the voxel type and returns its index
+	/// if the requested voxel type is air, usize::MAX is returned instead
+	#[inline]
+	fn ensure_surface(&mut self, voxel: Voxel) -> usize {
+		if voxel == EMPTY {
+			return usize::MAX;
+		}
+		let key = if !self.texture_array {
+			SurfaceKind::Voxel(voxel)
+		} else if voxel.is_transparent() {
+			SurfaceKind::Transparent
+		} else {
+			SurfaceKind::Opaque
+		};
+		match self.surfaces.iter().position(|s| s.kind == key) {
+			Some(index) => index,
+			None => {
+				self.surfaces.push(Surface::new(key));
+				self.surfaces.len() - 1
+			}
+		}
+	}
+}
+
+impl Default for Mesher {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Surface {
+	fn new(kind: SurfaceKind) -> Self {
+		Self {
+			kind,
+			vertexes: Vec::new(),
+			normals: Vec::new(),
+			uvs: Vec::new(),
+			debug_uvs: Vec::new(),
+			tangents: Vec::new(),
+			colors: Vec::new(),
+		}
+	}
+
+	pub fn kind(&self) -> SurfaceKind {
+		self.kind
+	}
+
+	pub fn is_transparent(&self) -> bool {
+		match self.kind {
+			SurfaceKind::Voxel(voxel) => voxel.is_transparent(),
+			SurfaceKind::Opaque => false,
+			SurfaceKind::Transparent => true,
+		}
+	}
+
+	#[inline]
+	pub fn quad_count(&self) -> usize {
+		self.vertexes.len() / 6
+	}
+
+	pub fn vertexes(&self) -> &[Vec3] {
+		&self.vertexes
+	}
+
+	pub fn normals(&self) -> &[Vec3] {
+		&self.normals
+	}
+
+	pub fn uvs(&self) -> &[Vec2] {
+		&self.uvs
+	}
+
+	/// empty unless DEBUG_UVS is set
+	pub fn debug_uvs(&self) -> &[Vec2] {
+		&self.debug_uvs
+	}
+
+	pub fn tangents(&self) -> &[f32] {
+		&self.tangents
+	}
+
+	pub fn colors(&self) -> &[[f32; 4]] {
+		&self.colors
+	}
+
+	fn remove_quads_in_bound(&mut self, pos_min: Vec3, pos_max: Vec3) {
+		let mut quad_i = 0;
+		while quad_i < self.quad_count() {
+			if self.vertexes[quad_i * 6..quad_i * 6 + 6].iter().all(|&v| in_box(v, pos_min, pos_max)) {
+				self.remove_quad(quad_i);
+			}
+			else {
+				quad_i += 1;
+			}
+		}
+
+		#[inline]
+		fn in_box(v: Vec3, min: Vec3, max: Vec3) -> bool {
+			v.x <= max.x && v.x >= min.x
+			&& v.y <= max.y && v.y >= min.y
+			&& v.z <= max.z && v.z >= min.z
+		}
+	}
+
+	/// removes a quad by moving the last quad to its location
+	fn remove_quad(&mut self, to_remove: usize) {
+		let replacement = self.quad_count() - 1;
+		move_quad(&mut self.vertexes, replacement, to_remove, 1);
+		move_quad(&mut self.normals, replacement, to_remove, 1);
+		move_quad(&mut self.uvs, replacement, to_remove, 1);
+		move_quad(&mut self.tangents, replacement, to_remove, 4);
+		move_quad(&mut self.colors, replacement, to_remove, 1);
+		if DEBUG_UVS {
+			move_quad(&mut self.debug_uvs, replacement, to_remove, 1);
+		}
+
+		#[inline]
+		fn move_quad<T: Copy>(array: &mut Vec<T>, from: usize, to: usize, stride: usize) {
+			let len = 6 * stride;
+			array.copy_within(from * len..(from + 1) * len, to * len);
+			array.truncate(from * len);
+		}
+	}
+
+	#[inline]
+	fn push_vertex(&mut self, pos: Vec3, normal: Vec3, uv: Vec2, face: usize, color: [f32; 4], debug_uv: Vec2) {
+		self.vertexes.push(pos);
+		self.normals.push(normal);
+		self.uvs.push(uv);
+		self.tangents.extend_from_slice(&FACE_TANGENTS[face]);
+		self.colors.push(color);
+		if DEBUG_UVS {
+			self.debug_uvs.push(debug_uv);
+		}
+	}
+
+	/// vertex color alpha, the texture array layer on the shared surfaces
+	/// and the side of the cube on per type surfaces, as smooth and model normals do not tell the shader which texture to use
+	#[inline]
+	fn alpha(&self, voxel: Voxel, face: usize) -> f32 {
+		match self.kind {
+			SurfaceKind::Voxel(_) => face as f32,
+			_ => voxel.texture_layer(face) as f32,
+		}
+	}
+
+	/// add a quad from 4 verts, in the order: [0, 1, 2, 2, 3, 0]
+	/// or [1, 2, 3, 3, 0, 1] when that makes the ambient occlusion interpolate evenly
+	/// vertex color rgb is the ambient occlusion, alpha is the texture array layer or the face, see alpha
+	#[inline]
+	fn add_quad(&mut self, corners: [Vec3; 4], face: usize, voxel: Voxel, ao: [u8; 4]) {
+		let quad_offsets = if ao[0] + ao[2] < ao[1] + ao[3] {
+			QUAD_OFFSETS_FLIPPED
+		} else {
+			QUAD_OFFSETS
+		};
+		let alpha = self.alpha(voxel, face);
+		// whole quads at a time, this is where the greedy mesher spends most of its time
+		let uvs = corners.map(|c| face_uv(c, face));
+		let colors = ao.map(|ao| {
+			let light = AO_CURVE[ao as usize];
+			[light, light, light, alpha]
+		});
+		self.vertexes.extend_from_slice(&quad_offsets.map(|c| corners[c]));
+		self.normals.extend_from_slice(&[NORMALS[face]; 6]);
+		self.uvs.extend_from_slice(&quad_offsets.map(|c| uvs[c]));
+		self.tangents.extend_from_slice([FACE_TANGENTS[face]; 6].as_flattened());
+		self.colors.extend_from_slice(&quad_offsets.map(|c| colors[c]));
+		if DEBUG_UVS {
+			self.debug_uvs.extend_from_slice(&quad_offsets.map(|c| DEBUG_UVS_CORNERS[c]));
+		}
+	}
+
+	/// add a quad of a voxel model at pos, without ambient occlusion
+	fn add_model_quad(&mut self, pos: Vec3, quad: &ModelQuad, voxel: Voxel) {
+		let alpha = self.alpha(voxel, quad.face);
+		for v in 0..6 {
+			self.push_vertex(
+				pos + quad.verts[v],
+				quad.normals[v],
+				quad.uvs[v],
+				quad.face,
+				[1.0, 1.0, 1.0, alpha],
+				DEBUG_UVS_CORNERS[QUAD_OFFSETS[v]],
+			);
+		}
+	}
+
+	/// add a quad of a smooth mesh from 4 verts with their own normals, without ambient occlusion
+	/// uvs and tangents are projected from the side of a cube the quad replaces
+	fn add_smooth_quad(&mut self, corners: [Vec3; 4], normals: [Vec3; 4], face: usize, voxel: Voxel) {
+		let alpha = self.alpha(voxel, face);
+		for corner in QUAD_OFFSETS {
+			self.push_vertex(
+				corners[corner],
+				normals[corner],
+				face_uv(corners[corner], face),
+				face,
+				[1.0, 1.0, 1.0, alpha],
+				DEBUG_UVS_CORNERS[corner],
+			);
+		}
+	}
+
+	fn clear(&mut self) {
+		self.vertexes.clear();
+		self.normals.clear();
+		self.uvs.clear();
+		self.debug_uvs.clear();
+		self.tangents.clear();
+		self.colors.clear();
+	}
+}
+
+/// one bit per voxel in the chunk, as u32 rows along each axis
+pub(crate) struct BitGrid {
+	/// rows along axis i are indexed by the other two coordinates in x, y, z order
+	rows: [Vec<u32>; 3],
+}
+
+impl BitGrid {
+	pub(crate) fn new(core: &ChunkCore, f: impl Fn(Voxel) -> bool) -> Self {
+		let entries: Vec<bool> = core.palette().iter().map(|&v| f(v)).collect();
+		let [grid] = Self::from_palette(core, [&entries]);
+		grid
+	}
+
+	/// one grid per list of palette entries in it, all built in the same pass over the chunk
+	/// grids that all or none of the palette is in are filled without looking at the voxels
+	pub(crate) fn from_palette<const N: usize>(core: &ChunkCore, entries: [&[bool]; N]) -> [Self; N] {
+		let mixed = entries.map(|e| e.contains(&true) && e.contains(&false));
+		let mut grids = entries.map(|e| {
+			let row = if e.contains(&false) { 0 } else { u32::MAX };
+			Self { rows: [vec![row; AREA], vec![row; AREA], vec![row; AREA]] }
+		});
+		if !mixed.contains(&true) {
+			return grids;
+		}
+
+		// voxels are stored along z, so those rows are read first and the others are transposed from them
+		// one bit per grid for each palette entry, so every voxel needs one lookup
+		let flags: Vec<u32> = (0..entries[0].len())
+			.map(|entry| (0..N).map(|g| (entries[g][entry] as u32) << g).sum())
+			.collect();
+		let mut z_rows = [[0u32; N]; AREA];
+		core.for_each_palette_index(|i, entry| {
+			let flag = flags[entry];
+			for (g, row) in z_rows[i / WIDTH].iter_mut().enumerate() {
+				*row |= ((flag >> g) & 1) << (i % WIDTH);
+			}
+		});
+		for (g, grid) in grids.iter_mut().enumerate() {
+			for (row, rows) in grid.rows[2].iter_mut().zip(z_rows.iter()) {
+				*row = rows[g];
+			}
+		}
+		for (grid, mixed) in grids.iter_mut().zip(mixed) {
+			if mixed {
+				grid.transpose_z_rows();
+			}
+		}
+		grids
+	}
+
+	/// fill the x and y rows from the z rows
+	fn transpose_z_rows(&mut self) {
+		let mut block = [0; WIDTH];
+		for a in 0..WIDTH {
+			// the y rows at x = a
+			block.copy_from_slice(&self.rows[2][a * WIDTH..(a + 1) * WIDTH]);
+			transpose(&mut block);
+			self.rows[1][a * WIDTH..(a + 1) * WIDTH].copy_from_slice(&block);
+			// the x rows at y = a
+			for (x, row) in block.iter_mut().enumerate() {
+				*row = self.rows[2][x * WIDTH + a];
+			}
+			transpose(&mut block);
+			self.rows[0][a * WIDTH..(a + 1) * WIDTH].copy_from_slice(&block);
+		}
+	}
+
+	/// the bits along the offset axis of a face, see layered_pos
+	#[inline]
+	pub(crate) fn row(&self, face: usize, layer: usize, slice: usize) -> u32 {
+		match face {
+			0 | 1 => self.rows[2][layer * WIDTH + slice],
+			2 | 3 => self.rows[0][layer * WIDTH + slice],
+			_ => self.rows[1][slice * WIDTH + layer],
+		}
+	}
+}
+
+/// faces can be merged when they have the same voxel type and ambient occlusion
+/// the palette entry shifted up by 8 bits, and the ambient occlusion packed with pack_ao
+type MaskKey = usize;
+
+/// a merged rectangle of faces in one layer
+pub(crate) struct GreedyQuad {
+	slice_start: usize,
+	slice_end: usize,
+	offset_start: usize,
+	offset_end: usize,
+}
+
+impl GreedyQuad {
+	pub(crate) fn transformed_verts(&self, face: usize, layer: usize) -> [Vec3; 4] {
+		match face {
+			0 => [
+				uvec3(layer+1, self.slice_end, self.offset_end),
+				uvec3(layer+1, self.slice_end, self.offset_start),
+				uvec3(layer+1, self.slice_start, self.offset_start),
+				uvec3(layer+1, self.slice_start, self.offset_end),
+			],
+			1 => [
+				uvec3(layer, self.slice_end, self.offset_start),
+				uvec3(layer, self.slice_end, self.offset_end),
+				uvec3(layer, self.slice_start, self.offset_end),
+				uvec3(layer, self.slice_start, self.offset_start),
+			],
+			2 => [
+				uvec3(self.offset_start, layer+1, self.slice_start),
+				uvec3(self.offset_end, layer+1, self.slice_start),
+				uvec3(self.offset_end, layer+1, self.slice_end),
+				uvec3(self.offset_start, layer+1, self.slice_end),
+			],
+			3 => [
+				uvec3(self.offset_start, layer, self.slice_end),
+				uvec3(self.offset_end, layer, self.slice_end),
+				uvec3(self.offset_end, layer, self.slice_start),
+				uvec3(self.offset_start, layer, self.slice_start),
+			],
+			4 => [
+				uvec3(self.slice_start, self.offset_end, layer+1),
+				uvec3(self.slice_end, self.offset_end, layer+1),
+				uvec3(self.slice_end, self.offset_start, layer+1),
+				uvec3(self.slice_start, self.offset_start, layer+1),
+			],
+			5 => [
+				uvec3(self.slice_end, self.offset_end, layer),
+				uvec3(self.slice_start, self.offset_end, layer),
+				uvec3(self.slice_start, self.offset_start, layer),
+				uvec3(self.slice_end, self.offset_start, layer),
+			],
+			_ => panic!("invalid face index in GreedyQuad.transformed_verts")
+		}
+	}
+}
+
+/// for each slice of a layer, the faces that are covered by the opaque voxels in front of them
+fn covered_rows(core: &ChunkCore, opaque: &BitGrid, face: usize, layer: usize) -> [u32; WIDTH] {
+	let mut covered = [0; WIDTH];
+	let top_layer = if face.is_multiple_of(2) { layer + 1 } else { layer.wrapping_sub(1) };
+	if top_layer < WIDTH {
+		for (slice, row) in covered.iter_mut().enumerate() {
+			*row = opaque.row(face, top_layer, slice);
+		}
+	}
+	else {
+		// the layer in front is the border of the neighbouring chunk
+		// its rows are along x, x and y for the x, y and z faces, slices are along y, z and x
+		covered = *core.neighbour_opacity(face);
+		if face / 2 == 1 {
+			transpose(&mut covered);
+		}
+	}
+	covered
+}
+
+/// merge the set bits of `visible` into rectangles, calling `add` with each
+/// bits in `filler` may be covered too, but quads never start or end on them
+pub(crate) fn greedy_quads(mut visible: [u32; WIDTH], filler: &[u32; WIDTH], mut add: impl FnMut(GreedyQuad)) {
+	// quads never reach past the last slice with a visible face
+	let last_slice = WIDTH - visible.iter().rev().take_while(|&&row| row == 0).count();
+	for slice in 0..last_slice {
+		while visible[slice] != 0 {
+			let start = visible[slice].trailing_zeros() as usize;
+			let usable = visible[slice] | filler[slice];
+			let run = (!(usable >> start)).trailing_zeros() as usize;
+			let mut mask = bit_range(start, run);
+			let end = WIDTH - (visible[slice] & mask).leading_zeros() as usize;
+			mask = bit_range(start, end - start);
+
+			let mut slice_end = slice + 1;
+			while slice_end < last_slice && (visible[slice_end] | filler[slice_end]) & mask == mask {
+				slice_end += 1;
+			}
+			while visible[slice_end - 1] & mask == 0 {
+				slice_end -= 1;
+			}
+			for row in visible[slice..slice_end].iter_mut() {
+				*row &= !mask;
+			}
+			add(GreedyQuad {
+				slice_start: slice,
+				slice_end,
+				offset_start: start,
+				offset_end: end,
+			});
+		}
+	}
+}
+
+/// swap rows and bits of a 32x32 bit matrix, so bit j of row i becomes bit i of row j
+fn transpose(m: &mut [u32; WIDTH]) {
+	let mut width = WIDTH / 2;
+	let mut mask: u32 = 0x0000_ffff;
+	while width != 0 {
+		// swap the upper right and lower left blocks of every width x width square
+		let mut row = 0;
+		while row < WIDTH {
+			let swap = ((m[row] >> width) ^ m[row + width]) & mask;
+			m[row + width] ^= swap;
+			m[row] ^= swap << width;
+			row = (row + width + 1) & !width;
+		}
+		width /= 2;
+		mask ^= mask << width;
+	}
+}
+
+/// `len` set bits starting at bit `start`
+#[inline]
+fn bit_range(start: usize, len: usize) -> u32 {
+	if len >= WIDTH {
+		u32::MAX
+	} else {
+		((1 << len) - 1) << start
+	}
+}
+
+
+/// ambient occlusion level of each corner of a face, from 0 (fully occluded) to 3
+/// based on the three voxels touching the corner in front of the face, `is_opaque` must also work outside the chunk
+#[inline]
+fn face_ao(pos: VoxelPos, face: usize, is_opaque: impl Fn(VoxelPos) -> bool) -> [u8; 4] {
+	let axis = face / 2;
+	let (u, w) = ((axis + 1) % 3, (axis + 2) % 3);
+	let normal = NORMALS_I8[face];
+	let front = [pos.0 + normal.0, pos.1 + normal.1, pos.2 + normal.2];
+	let opaque = |p: [i8; 3]| is_opaque((p[0], p[1], p[2])) as u8;
+
+	let mut ao = [0; 4];
+	for (i, corner) in FACE_VERTS[face].iter().enumerate() {
+		let corner = [corner.x as i8, corner.y as i8, corner.z as i8];
+		let mut side_u = front;
+		side_u[u] += corner[u] * 2 - 1;
+		let mut side_w = front;
+		side_w[w] += corner[w] * 2 - 1;
+		let mut diagonal = side_u;
+		diagonal[w] = side_w[w];
+
+		let (side_u, side_w) = (opaque(side_u), opaque(side_w));
+		ao[i] = if side_u == 1 && side_w == 1 {
+			0
+		} else {
+			3 - side_u - side_w - opaque(diagonal)
+		};
+	}
+	ao
+}
+
+/// two bits for each corner, in corner order from the lowest bits
+#[inline]
+fn pack_ao(ao: [u8; 4]) -> u8 {
+	ao[0] | ao[1] << 2 | ao[2] << 4 | ao[3] << 6
+}
+
+#[inline]
+fn unpack_ao(packed: u8) -> [u8; 4] {
+	[0, 2, 4, 6].map(|shift| (packed >> shift) & 3)
+}
+
+/// packed ambient occlusion of a face for every combination of the 3x3 voxels in front of it
+/// indexed by face and 9 bits, three per slice with one per offset, see layered_pos
+fn ao_table() -> &'static [[u8; 512]; 6] {
+	static TABLE: OnceLock<[[u8; 512]; 6]> = OnceLock::new();
+	TABLE.get_or_init(|| {
+		let mut table = [[0; 512]; 6];
+		for (face, face_table) in table.iter_mut().enumerate() {
+			let center = WIDTH / 2;
+			for (bits, ao) in face_table.iter_mut().enumerate() {
+				let is_opaque = |pos: VoxelPos| {
+					let (slice, offset) = slice_offset(face, pos).unwrap();
+					(bits >> ((slice + 1 - center) * 3 + offset + 1 - center)) & 1 == 1
+				};
+				*ao = pack_ao(face_ao(layered_pos(face, center, center, center), face, is_opaque));
+			}
+		}
+		table
+	})
+}
+
+/// samples per axis for the smooth mesher: the chunk and one voxel of the neighbours on each side
+const SAMPLES: usize = WIDTH + 2;
+/// cells per axis for the smooth mesher, one between every two samples
+const CELLS: usize = SAMPLES - 1;
+
+/// the 12 edges of a cell, as pairs of corners in x, y, z bit order
+const CELL_EDGES: [(usize, usize); 12] = [
+	(0b000, 0b100), (0b010, 0b110), (0b001, 0b101), (0b011, 0b111),
+	(0b000, 0b010), (0b100, 0b110), (0b001, 0b011), (0b101, 0b111),
+	(0b000, 0b001), (0b100, 0b101), (0b010, 0b011), (0b110, 0b111)];
+
+#[inline]
+fn sample_index(x: usize, y: usize, z: usize) -> usize {
+	(x * SAMPLES + y) * SAMPLES + z
+}
+
+#[inline]
+fn cell_index(x: usize, y: usize, z: usize) -> usize {
+	(x * CELLS + y) * CELLS + z
+}
+
+/// position and normal of the vertex of a smooth mesh cell, in chunk space
+/// None if the cell is not on the surface
+fn cell_vertex(opaque: &[bool], x: usize, y: usize, z: usize) -> Option<(Vec3, Vec3)> {
+	let corner_offset = |corner: usize| ((corner >> 2) & 1, (corner >> 1) & 1, corner & 1);
+	let corner_opaque = |corner: usize| {
+		let (cx, cy, cz) = corner_offset(corner);
+		opaque[sample_index(x + cx, y + cy, z + cz)]
+	};
+	let corner_pos = |corner: usize| {
+		let (cx, cy, cz) = corner_offset(corner);
+		uvec3(cx, cy, cz)
+	};
+
+	let mut sum = Vec3::ZERO;
+	let mut crossings = 0;
+	for (a, b) in CELL_EDGES {
+		if corner_opaque(a) != corner_opaque(b) {
+			sum += (corner_pos(a) + corner_pos(b)) * 0.5;
+			crossings += 1;
+		}
+	}
+	if crossings == 0 {
+		return None;
+	}
+	// the normal points from opaque to transparent corners
+	let mut normal = Vec3::ZERO;
+	for corner in 0..8 {
+		if corner_opaque(corner) {
+			normal -= corner_pos(corner) - Vec3::ONE * 0.5;
+		}
+	}
+	let normal = if normal.length_squared() > 0.0 { normal.normalized() } else { Vec3::UP };
+	// cell corners are on voxel centers, which are half a voxel from the voxel corners
+	let pos = uvec3(x, y, z) - Vec3::ONE * 0.5 + sum / crossings as f32;
+	Some((pos, normal))
+}
+
+/// texture coordinates of a vertex, in voxels
+/// textures are upright on the side faces and repeat once per voxel
+#[inline]
+pub fn face_uv(pos: Vec3, face: usize) -> Vec2 {
+	match face {
+		0 => Vec2::new(-pos.z, -pos.y),
+		1 => Vec2::new(pos.z, -pos.y),
+		2 => Vec2::new(pos.x, pos.z),
+		3 => Vec2::new(-pos.x, pos.z),
+		4 => Vec2::new(pos.x, -pos.y),
+		5 => Vec2::new(-pos.x, -pos.y),
+		_ => panic!("invalid face index for face_uv()")
+	}
+}
+
+fn layered_pos(face: usize, layer: usize, slice: usize, offset: usize) -> VoxelPos {
+	match face {
+		0 | 1 => (layer as i8, slice as i8, offset as i8),
+		2 | 3 => (offset as i8, layer as i8, slice as i8),
+		4 | 5 => (slice as i8, offset as i8, layer as i8),
+		_ => panic!("invalid face index for layered_pos()")
+	}
+}
+
+/// slice and offset of a position, the reverse of layered_pos without the layer
+/// None if either is outside the chunk
+#[inline]
+fn slice_offset(face: usize, vpos: VoxelPos) -> Option<(usize, usize)> {
+	let (slice, offset) = match face {
+		0 | 1 => (vpos.1, vpos.2),
+		2 | 3 => (vpos.2, vpos.0),
+		_ => (vpos.0, vpos.1),
+	};
+	let inside = |v: i8| (0..WIDTH_I8).contains(&v);
+	if inside(slice) && inside(offset) {
+		Some((slice as usize, offset as usize))
+	} else {
+		None
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use std::collections::{HashMap, HashSet};
+
+	use super::*;
+	use crate::terrain::{Noise, TerrainGenerator};
+	use crate::types::test_types::{self, GLASS, PLANT, SLAB};
+
+	/// a unit face: the face direction and the voxel it belongs to
+	type UnitFace = (usize, (i32, i32, i32));
+
+	struct Waves;
+
+	impl Noise for Waves {
+		fn noise_2d(&self, x: f64, y: f64) -> f64 {
+			(x * 0.3).sin() * 0.5 + (y * 0.2).cos() * 0.4
+		}
+	}
+
+	fn quads(mesher: &Mesher) -> usize {
+		mesher.surfaces().map(|s| s.quad_count()).sum()
+	}
+
+	/// split every quad into the voxel faces it covers
+	fn unit_faces(mesher: &Mesher) -> Vec<UnitFace> {
+		lit_unit_faces(mesher).into_iter().map(|(face, _)| face).collect()
+	}
+
+	/// unit_faces with the brightness of the vertices of the quad each face is part of, sorted
+	fn lit_unit_faces(mesher: &Mesher) -> Vec<(UnitFace, [u32; 6])> {
+		let mut faces = Vec::new();
+		for surface in mesher.surfaces() {
+			let quads = surface.vertexes().chunks(6).zip(surface.normals().chunks(6)).zip(surface.colors().chunks(6));
+			for ((quad, normals), colors) in quads {
+				let mut light: [u32; 6] = std::array::from_fn(|i| colors[i][0].to_bits());
+				light.sort();
+				let normal = normals[0];
+				let face = (0..6).find(|&f| NORMALS[f] == normal).unwrap();
+				let axis = face / 2;
+				let min = quad.iter().fold(quad[0], |a, b| Vec3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)));
+				let max = quad.iter().fold(quad[0], |a, b| Vec3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)));
+				let range = |a: usize| {
+					if a == axis {
+						// the voxel is behind the face
+						let plane = min.get(a) as i32;
+						if face.is_multiple_of(2) { plane - 1..plane } else { plane..plane + 1 }
+					} else {
+						min.get(a) as i32..max.get(a) as i32
+					}
+				};
+				for x in range(0) {
+					for y in range(1) {
+						for z in range(2) {
+							faces.push(((face, (x, y, z)), light));
+						}
+					}
+				}
+			}
+		}
+		faces
+	}
+
+	fn terrain_chunk() -> ChunkCore {
+		let generator = TerrainGenerator::new(Waves, Waves, Waves, Waves);
+		let mut core = generator.generate(ivec3(0, -1, 0) * WIDTH_F + ivec3(0, 16, 0));
+		// some holes and overhangs
+		for i in (0..VOLUME).step_by(37) {
+			core.set_voxel(index_to_vposv(i), EMPTY);
+		}
+		core
+	}
+
+	#[test]
+	fn empty_chunk_has_no_surfaces() {
+		let mut mesher = Mesher::new();
+		mesher.generate_greedy(&ChunkCore::new(), false);
+		assert_eq!(mesher.surfaces().count(), 0);
+		assert!(mesher.is_inited());
+	}
+
+	#[test]
+	fn single_voxel() {
+		let mut core = ChunkCore::new();
+		core.set_voxel(ivec3(4, 5, 6), 1);
+		let mut mesher = Mesher::new();
+		mesher.generate_greedy(&core, false);
+		assert_eq!(quads(&mesher), 6);
+		let faces: HashSet<UnitFace> = unit_faces(&mesher).into_iter().collect();
+		assert_eq!(faces, (0..6).map(|f| (f, (4, 5, 6))).collect());
+	}
+
+	#[test]
+	fn full_chunk_is_one_quad_per_side() {
+		let mut mesher = Mesher::new();
+		mesher.generate_greedy(&ChunkCore::new_filled(1), false);
+		assert_eq!(quads(&mesher), 6);
+		assert_eq!(unit_faces(&mesher).len(), 6 * AREA);
+	}
+
+	#[test]
+	fn opaque_neighbours_cull_border_faces() {
+		let core_a = ChunkCore::new_filled(1);
+		let mut core_b = ChunkCore::new_filled(2);
+		core_b.set_neighbour(1, core_a.border(0));
+		let mut mesher = Mesher::new();
+		mesher.generate_greedy(&core_b, false);
+		assert_eq!(quads(&mesher), 5);
+		assert!(unit_faces(&mesher).iter().all(|(face, _)| *face != 1));
+	}
+
+	#[test]
+	fn buffers_have_matching_lengths() {
+		let mut mesher = Mesher::new();
+		mesher.generate_greedy(&terrain_chunk(), false);
+		for s in mesher.surfaces() {
+			let verts = s.vertexes().len();
+			assert_eq!(verts, s.quad_count() * 6);
+			assert_eq!(s.normals().len(), verts);
+			assert_eq!(s.uvs().len(), verts);
+			assert_eq!(s.tangents().len(), verts * 4);
+			assert_eq!(s.colors().len(), verts);
+			if DEBUG_UVS {
+				assert_eq!(s.debug_uvs().len(), verts);
+			}
+		}
+	}
+
+	#[test]
+	fn greedy_covers_the_same_faces_as_fast() {
+		let core = terrain_chunk();
+		let mut fast = Mesher::new();
+		fast.generate_fast(&core, false);
+		let mut greedy = Mesher::new();
+		greedy.generate_greedy(&core, false);
+
+		let fast_faces: HashSet<UnitFace> = unit_faces(&fast).into_iter().collect();
+		let greedy_faces = unit_faces(&greedy);
+		let greedy_set: HashSet<UnitFace> = greedy_faces.iter().copied().collect();
+		assert_eq!(greedy_faces.len(), greedy_set.len(), "greedy quads overlap");
+		assert!(fast_faces.is_subset(&greedy_set), "greedy mesh is missing faces");
+		// anything extra must be hidden behind an opaque voxel
+		for &(face, (x, y, z)) in greedy_set.difference(&fast_faces) {
+			let n = NORMALS_I8[face];
+			assert!(core.is_opaque_at((x as i8 + n.0, y as i8 + n.1, z as i8 + n.2)));
+		}
+		assert!(quads(&greedy) < quads(&fast));
+	}
+
+	#[test]
+	fn greedy_ambient_occlusion_matches_fast() {
+		// the chunk repeated in every direction, so faces on the border are shaded by the neighbours
+		let mut core = terrain_chunk();
+		for face in 0..6 {
+			core.set_neighbour(face, core.border(face ^ 1));
+		}
+		for (diagonal, offset) in DIAGONALS.iter().enumerate() {
+			core.set_diagonal(diagonal, core.diagonal_opacity((-offset.0, -offset.1, -offset.2)));
+		}
+		let mut fast = Mesher::new();
+		fast.generate_fast(&core, false);
+		let mut greedy = Mesher::new();
+		greedy.generate_greedy(&core, false);
+
+		let greedy_faces: HashMap<UnitFace, [u32; 6]> = lit_unit_faces(&greedy).into_iter().collect();
+		for (face, light) in lit_unit_faces(&fast) {
+			assert_eq!(greedy_faces.get(&face), Some(&light), "different shading on {:?}", face);
+		}
+	}
+
+	#[test]
+	fn voxel_types_get_their_own_surfaces() {
+		let mut core = ChunkCore::new();
+		core.set_voxel(ivec3(0, 0, 0), 1);
+		core.set_voxel(ivec3(1, 0, 0), 2);
+		let mut mesher = Mesher::new();
+		mesher.generate_greedy(&core, false);
+		let kinds: Vec<SurfaceKind> = mesher.surfaces().map(|s| s.kind()).collect();
+		assert_eq!(kinds.len(), 2);
+		assert!(kinds.contains(&SurfaceKind::Voxel(1)));
+		assert!(kinds.contains(&SurfaceKind::Voxel(2)));
+		// the faces between them are hidden
+		assert_eq!(quads(&mesher), 10);
+
+		mesher.generate_greedy(&core, true);
+		let kinds: Vec<SurfaceKind> = mesher.surfaces().map(|s| s.kind()).collect();
+		assert_eq!(kinds, vec![SurfaceKind::Opaque]);
+	}
+
+	#[test]
+	fn transparent_faces_of_the_same_type_are_culled() {
+		test_types::init();
+		let mut core = ChunkCore::new();
+		core.set_voxel(ivec3(0, 0, 0), GLASS);
+		core.set_voxel(ivec3(1, 0, 0), GLASS);
+		core.set_voxel(ivec3(0, 1, 0), 1);
+		let mut mesher = Mesher::new();
+		mesher.generate_greedy(&core, true);
+		let glass = mesher.surfaces().find(|s| s.kind() == SurfaceKind::Transparent).unwrap();
+		// 2x1 top is hidden where the cube is, so the glass has 2 long sides, 2 ends, a bottom and one top face
+		assert_eq!(glass.quad_count(), 6);
+		assert!(glass.is_transparent());
+		// transparent surfaces are drawn last
+		assert_eq!(mesher.surfaces().last().unwrap().kind(), SurfaceKind::Transparent);
+	}
+
+	#[test]
+	fn models_are_added_after_cubes() {
+		test_types::init();
+		let mut core = ChunkCore::new();
+		core.set_voxel(ivec3(3, 3, 3), SLAB);
+		let mut mesher = Mesher::new();
+		mesher.generate_greedy(&core, false);
+		let slab = mesher.surfaces().next().unwrap();
+		assert_eq!(slab.kind(), SurfaceKind::Voxel(SLAB));
+		assert_eq!(slab.quad_count(), 6);
+		assert!(slab.vertexes().iter().all(|v| v.y <= 3.5));
+	}
+
+	#[test]
+	fn cross_quads_keep_their_side_without_texture_array() {
+		test_types::init();
+		let mut core = ChunkCore::new();
+		core.set_voxel(ivec3(3, 3, 3), PLANT);
+		let mut mesher = Mesher::new();
+		mesher.generate_greedy(&core, false);
+		let plant = mesher.surfaces().next().unwrap();
+		assert_eq!(plant.kind(), SurfaceKind::Voxel(PLANT));
+		// diagonal normals, so the per type shaders use the side in vertex color alpha
+		assert!(plant.normals().iter().all(|n| n.x.abs() < 0.9 && n.z.abs() < 0.9));
+		assert!(plant.colors().iter().all(|c| c[3] == 0.0));
+	}
+
+	#[test]
+	fn partial_remesh_matches_full_mesh() {
+		let mut core = terrain_chunk();
+		let mut mesher = Mesher::new();
+		mesher.generate_fast(&core, false);
+		let edits = [(ivec3(5, 10, 5), EMPTY), (ivec3(6, 20, 9), 3), (ivec3(0, 0, 0), EMPTY), (ivec3(5, 10, 5), 2)];
+		for (pos, voxel) in edits {
+			let old_voxel = core.get_voxel(pos);
+			core.set_voxel(pos, voxel);
+			mesher.remesh_partial(&core, pos, old_voxel);
+		}
+		let mut full = Mesher::new();
+		full.generate_fast(&core, false);
+		let partial: HashSet<UnitFace> = unit_faces(&mesher).into_iter().collect();
+		let expected: HashSet<UnitFace> = unit_faces(&full).into_iter().collect();
+		assert_eq!(partial.difference(&expected).collect::<Vec<_>>(), Vec::<&UnitFace>::new());
+		assert_eq!(expected.difference(&partial).collect::<Vec<_>>(), Vec::<&UnitFace>::new());
+		assert_eq!(quads(&mesher), quads(&full));
+	}
+
+	#[test]
+	fn smooth_mesh_is_closed() {
+		let mut core = ChunkCore::new();
+		for x in 10..14 {
+			for y in 10..14 {
+				for z in 10..14 {
+					core.set_voxel(ivec3(x, y, z), 1);
+				}
+			}
+		}
+		let mut mesher = Mesher::new();
+		mesher.generate_smooth(&core, false);
+		// one quad per face of the cube
+		assert_eq!(quads(&mesher), 6 * 16);
+		for s in mesher.surfaces() {
+			for v in s.vertexes() {
+				assert!((9.5..=14.5).contains(&v.x) && (9.5..=14.5).contains(&v.y) && (9.5..=14.5).contains(&v.z));
+			}
+		}
+	}
+
+	#[test]
+	fn smooth_faces_keep_their_side_without_texture_array() {
+		let mut mesher = Mesher::new();
+		mesher.generate_smooth(&terrain_chunk(), false);
+		let mut sloped = 0;
+		for s in mesher.surfaces() {
+			assert!(matches!(s.kind(), SurfaceKind::Voxel(_)));
+			for (color, normal) in s.colors().iter().zip(s.normals()) {
+				// the per type shaders pick the texture for this side, whatever the normal is
+				assert!(color[3].fract() == 0.0 && (0.0..6.0).contains(&color[3]));
+				let n = normal.abs();
+				if n.x.max(n.y).max(n.z) < 0.9 {
+					sloped += 1;
+				}
+			}
+		}
+		assert!(sloped > 0);
+	}
+
+	/// reports how long the greedy mesher takes per chunk, it does not assert a target
+	/// on one core in a release build: terrain 0.6-0.8ms, stone 0.7-0.85ms, filled 0.13-0.17ms, empty ~1µs,
+	/// caves 1.3-1.45ms and caves with glass 2.2-2.6ms
+	/// the cave chunks have 6-12k quads and about half of their time is spent writing vertex buffers
+	/// timings in debug builds mean nothing, run with `cargo test --release -- --nocapture` to see them
+	#[test]
+	#[cfg_attr(debug_assertions, ignore = "timing, needs a release build")]
+	fn greedy_mesh_benchmark() {
+		use std::time::Instant;
+		test_types::init();
+		const RUNS: u32 = 50;
+		const BATCHES: usize = 5;
+
+		let generator = TerrainGenerator::new(Waves, Waves, Waves, Waves);
+		let mut stone = ChunkCore::new();
+		for i in 0..VOLUME {
+			stone.set_voxel(index_to_vposv(i), 1 + (i % 3) as Voxel);
+		}
+		let mut glassy = terrain_chunk();
+		for i in (0..VOLUME).step_by(11) {
+			if glassy.get_voxel_index(i) != EMPTY {
+				glassy.set_voxel(index_to_vposv(i), GLASS);
+			}
+		}
+		let chunks = [
+			("terrain", generator.generate(ivec3(0, -1, 0) * WIDTH_F + ivec3(0, 16, 0))),
+			("stone", stone),
+			("filled", ChunkCore::new_filled(1)),
+			("empty", ChunkCore::new()),
+			("caves", terrain_chunk()),
+			("caves with glass", glassy),
+		];
+		let mut mesher = Mesher::new();
+		for (name, core) in chunks.iter() {
+			mesher.generate_greedy(core, false);
+			// the fastest batch, so other work on the machine has less effect
+			let time = (0..BATCHES).map(|_| {
+				let start = Instant::now();
+				for _ in 0..RUNS {
+					mesher.generate_greedy(core, false);
+				}
+				start.elapsed() / RUNS
+			}).min().unwrap();
+			println!("greedy mesh of {} chunk: {:?}, {} quads", name, time, quads(&mesher));
+		}
+	}
+}
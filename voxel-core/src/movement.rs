@@ -0,0 +1,212 @@
+use crate::math::{Aabb, Vec3};
+
+
+/// how far a box may overlap a voxel before it counts as touching it, to make up for rounding errors
+const EPSILON: f32 = 0.001;
+
+/// the result of move_and_slide_aabb
+#[derive(Clone, PartialEq, Debug)]
+pub struct SlideMotion {
+	/// the box after moving
+	pub aabb: Aabb,
+	/// how far the box moved
+	pub motion: Vec3,
+	/// the velocity with the parts that went into voxels removed
+	pub velocity: Vec3,
+	/// standing on a voxel, only detected while moving down, eg. from gravity
+	pub grounded: bool,
+	/// normals of the voxel faces the box ran into, at most one per axis
+	pub normals: Vec<Vec3>,
+}
+
+/// moves a box by velocity * delta, sliding along the voxels `is_solid` returns true for
+/// every solid voxel is treated as a full cube
+/// while grounded, the box climbs ledges up to step_height high, 0 disables stepping
+/// the axes are resolved one at a time, y first, so the result only depends on the arguments
+pub fn move_and_slide_aabb(aabb: Aabb, velocity: Vec3, delta: f32, step_height: f32, mut is_solid: impl FnMut(Vec3) -> bool) -> SlideMotion {
+	let motion = velocity * delta;
+	let plain = slide(aabb, motion, velocity, [1, 0, 2], &mut is_solid);
+	let blocked_sideways = plain.normals.iter().any(|n| n.y == 0.0);
+	if step_height <= 0.0 || !plain.grounded || !blocked_sideways {
+		return plain;
+	}
+
+	// lift the box, move it sideways and put it back down
+	let lift = sweep(aabb, 1, step_height, &mut is_solid);
+	let lifted = Aabb::new(aabb.pos + Vec3::UP * lift, aabb.size);
+	let mut stepped = slide(lifted, motion.with(1, motion.y - lift), velocity, [0, 2, 1], &mut is_solid);
+	stepped.motion = stepped.aabb.pos - aabb.pos;
+
+	let sideways = |m: Vec3| m.x * m.x + m.z * m.z;
+	if stepped.grounded && sideways(stepped.motion) > sideways(plain.motion) {
+		stepped
+	}
+	else {
+		plain
+	}
+}
+
+/// move along each axis in order, stopping at the first solid voxel on each
+fn slide(mut aabb: Aabb, motion: Vec3, mut velocity: Vec3, order: [usize; 3], is_solid: &mut impl FnMut(Vec3) -> bool) -> SlideMotion {
+	let start = aabb.pos;
+	let mut grounded = false;
+	let mut normals = Vec::new();
+	for axis in order {
+		let distance = motion.get(axis);
+		let moved = sweep(aabb, axis, distance, is_solid);
+		aabb.pos = aabb.pos.with(axis, aabb.pos.get(axis) + moved);
+		if moved != distance {
+			velocity = velocity.with(axis, 0.0);
+			normals.push(Vec3::axis(axis) * -distance.signum());
+			grounded |= axis == 1 && distance < 0.0;
+		}
+	}
+	SlideMotion {
+		aabb,
+		motion: aabb.pos - start,
+		velocity,
+		grounded,
+		normals,
+	}
+}
+
+/// how far the box can move along one axis, up to distance
+fn sweep(aabb: Aabb, axis: usize, distance: f32, is_solid: &mut impl FnMut(Vec3) -> bool) -> f32 {
+	if distance == 0.0 {
+		return 0.0;
+	}
+	let (a1, a2) = ((axis + 1) % 3, (axis + 2) % 3);
+	// voxels the box overlaps on an axis, faces that only touch do not count
+	let cells = |a: usize| {
+		let first = (aabb.pos.get(a) + EPSILON).floor() as i32;
+		let last = (aabb.end().get(a) - EPSILON).ceil() as i32 - 1;
+		first..=last
+	};
+	let mut layer_is_solid = |layer: i32| {
+		for i in cells(a1) {
+			for j in cells(a2) {
+				let pos = Vec3::ZERO
+					.with(axis, layer as f32)
+					.with(a1, i as f32)
+					.with(a2, j as f32);
+				if is_solid(pos) {
+					return true;
+				}
+			}
+		}
+		false
+	};
+
+	if distance > 0.0 {
+		let front = aabb.end().get(axis);
+		let first = (front - EPSILON).ceil() as i32;
+		let last = (front + distance).ceil() as i32 - 1;
+		for layer in first..=last {
+			if layer_is_solid(layer) {
+				return (layer as f32 - front).clamp(0.0, distance);
+			}
+		}
+	}
+	else {
+		let front = aabb.pos.get(axis);
+		let first = (front + EPSILON).floor() as i32 - 1;
+		let last = (front + distance).floor() as i32;
+		for layer in (last..=first).rev() {
+			if layer_is_solid(layer) {
+				return ((layer + 1) as f32 - front).clamp(distance, 0.0);
+			}
+		}
+	}
+	distance
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::common::*;
+
+	const PLAYER: Vec3 = Vec3::new(0.6, 1.8, 0.6);
+	const GRAVITY: Vec3 = Vec3::new(0.0, -10.0, 0.0);
+
+	/// a floor below y = 0, a one voxel high step at x = 3 and a wall at x >= 6
+	fn world(pos: Vec3) -> bool {
+		pos.y < 0.0 || (pos.x == 3.0 && pos.y == 0.0) || pos.x >= 6.0
+	}
+
+	fn player_at(x: f32, y: f32, z: f32) -> Aabb {
+		Aabb::new(Vec3::new(x, y, z), PLAYER)
+	}
+
+	#[test]
+	fn moves_freely_in_air() {
+		let velocity = Vec3::new(1.0, 2.0, -3.0);
+		let slide = move_and_slide_aabb(player_at(0.2, 5.0, 0.2), velocity, 0.5, 0.0, world);
+		assert_eq!(slide.motion, velocity * 0.5);
+		assert_eq!(slide.velocity, velocity);
+		assert!(!slide.grounded);
+		assert!(slide.normals.is_empty());
+	}
+
+	#[test]
+	fn lands_on_the_floor() {
+		let slide = move_and_slide_aabb(player_at(0.2, 0.3, 0.2), GRAVITY, 0.1, 0.0, world);
+		assert_eq!(slide.aabb.pos.y, 0.0);
+		assert_eq!(slide.velocity, Vec3::ZERO);
+		assert!(slide.grounded);
+		assert_eq!(slide.normals, vec![ivec3(0, 1, 0)]);
+
+		// standing still on it stays grounded
+		let slide = move_and_slide_aabb(slide.aabb, GRAVITY, 0.1, 0.0, world);
+		assert_eq!(slide.motion, Vec3::ZERO);
+		assert!(slide.grounded);
+	}
+
+	#[test]
+	fn slides_along_walls() {
+		let velocity = Vec3::new(10.0, 0.0, 10.0);
+		let slide = move_and_slide_aabb(player_at(5.0, 1.0, 0.0), velocity, 0.1, 0.0, world);
+		assert!((slide.aabb.end().x - 6.0).abs() < EPSILON);
+		assert_eq!(slide.motion.z, 1.0);
+		assert_eq!(slide.velocity, Vec3::new(0.0, 0.0, 10.0));
+		assert_eq!(slide.normals, vec![ivec3(-1, 0, 0)]);
+
+		// touching the wall does not slow down moving along it
+		let slide = move_and_slide_aabb(slide.aabb, Vec3::new(0.0, 0.0, 10.0), 0.1, 0.0, world);
+		assert_eq!(slide.motion, Vec3::new(0.0, 0.0, 1.0));
+		assert!(slide.normals.is_empty());
+	}
+
+	#[test]
+	fn does_not_tunnel_through_thin_walls() {
+		let slide = move_and_slide_aabb(player_at(1.0, 0.0, 0.0), Vec3::new(100.0, 0.0, 0.0), 1.0, 0.0, world);
+		assert_eq!(slide.aabb.end().x, 3.0);
+	}
+
+	#[test]
+	fn steps_up_ledges() {
+		let velocity = Vec3::new(5.0, -10.0, 0.0);
+		let start = player_at(2.3, 0.0, 0.2);
+		let blocked = move_and_slide_aabb(start, velocity, 0.1, 0.0, world);
+		assert_eq!(blocked.aabb.end().x, 3.0);
+		assert_eq!(blocked.aabb.pos.y, 0.0);
+
+		let stepped = move_and_slide_aabb(start, velocity, 0.1, 1.0, world);
+		assert!((stepped.aabb.pos.x - 2.8).abs() < EPSILON);
+		assert_eq!(stepped.aabb.pos.y, 1.0);
+		assert!(stepped.grounded);
+
+		// too high to climb
+		let too_high = move_and_slide_aabb(start, velocity, 0.1, 0.5, world);
+		assert_eq!(too_high, blocked);
+	}
+
+	#[test]
+	fn bumps_into_ceilings() {
+		let ceiling = |pos: Vec3| pos.y >= 2.0;
+		let slide = move_and_slide_aabb(player_at(0.0, 0.0, 0.0), Vec3::new(0.0, 5.0, 0.0), 1.0, 0.0, ceiling);
+		assert!((slide.aabb.end().y - 2.0).abs() < EPSILON);
+		assert!(!slide.grounded);
+		assert_eq!(slide.normals, vec![ivec3(0, -1, 0)]);
+	}
+}
@@ -0,0 +1,593 @@
+use std::collections::HashSet;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use crate::common::*;
+use crate::math::Vec3;
+
+
+/// the result of cast_ray
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RayHit {
+	pub hit: bool,
+	/// where the ray entered the voxel it stopped at, or where it ended
+	pub pos: Vec3,
+	/// the face of the voxel the ray entered through, zero if it started inside the voxel
+	pub normal: Vec3,
+	pub voxel: Voxel,
+	pub distance: f32,
+	/// the voxel that was hit, or the last one the ray went through, in whole numbers
+	pub voxel_pos: Vec3,
+	/// the voxel the ray went through before voxel_pos, eg. to place a new voxel against the hit one
+	pub previous_pos: Vec3,
+	/// the ray stopped at voxel_pos because it is not loaded, see RayFilter::stop_at_unloaded
+	pub unloaded: bool,
+}
+
+/// which voxels a ray stops at, air is never hit
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct RayFilter {
+	pub ignore_transparent: bool,
+	/// go through voxel types that are not selectable, see VoxelProperties::selectable
+	pub ignore_unselectable: bool,
+	/// if not empty, every other type is ignored
+	pub hit_only: Vec<Voxel>,
+	pub ignore: Vec<Voxel>,
+	/// stop when the ray enters a voxel that is not loaded, instead of going through it like air
+	pub stop_at_unloaded: bool,
+}
+
+impl RayFilter {
+	pub fn hits(&self, voxel: Voxel) -> bool {
+		if voxel == EMPTY || self.ignore.contains(&voxel) {
+			return false;
+		}
+		if self.ignore_transparent && voxel.is_transparent() {
+			return false;
+		}
+		if self.ignore_unselectable && !voxel.is_selectable() {
+			return false;
+		}
+		self.hit_only.is_empty() || self.hit_only.contains(&voxel)
+	}
+}
+
+/// rays starting further out on any axis are not cast, so voxel coordinates stay well inside i32 along the whole ray
+pub const MAX_SOURCE: f32 = 1.0e9;
+
+/// casts a ray, stopping at the first voxel `get_voxel` returns something other than air for
+/// see cast_ray_filtered
+pub fn cast_ray(source: Vec3, dir: Vec3, max_len: f32, mut get_voxel: impl FnMut(Vec3) -> Voxel) -> RayHit {
+	cast_ray_filtered(source, dir, max_len, &RayFilter::default(), |pos| Some(get_voxel(pos)))
+}
+
+/// casts a ray, stopping at the first voxel the filter hits
+/// `get_voxel` is called with the lowest corner of each voxel, and returns None for voxels that are not loaded
+/// voxels are visited one at a time through their faces, so none are skipped at grazing angles
+/// max_len is clamped to 0.001..65536.0
+/// nothing is visited when source, dir or max_len is not finite, dir is zero, or source is further than MAX_SOURCE from the origin
+pub fn cast_ray_filtered(source: Vec3, dir: Vec3, max_len: f32, filter: &RayFilter, mut get_voxel: impl FnMut(Vec3) -> Option<Voxel>) -> RayHit {
+	// Some((hit, voxel)) if the ray stops at pos, hit is false for unloaded voxels
+	let mut stop_at = |pos: Vec3| match get_voxel(pos) {
+		Some(voxel) if filter.hits(voxel) => Some((true, voxel)),
+		None if filter.stop_at_unloaded => Some((false, EMPTY)),
+		_ => None,
+	};
+	let dir = dir.normalized();
+	// false for NaN too
+	let within = |v: Vec3, limit: f32| v.x.abs() <= limit && v.y.abs() <= limit && v.z.abs() <= limit;
+	if !within(source, MAX_SOURCE) || !within(dir, f32::MAX) || !max_len.is_finite() {
+		return RayHit {
+			hit: false,
+			pos: source,
+			normal: Vec3::ZERO,
+			voxel: EMPTY,
+			distance: 0.0,
+			voxel_pos: Vec3::ZERO,
+			previous_pos: Vec3::ZERO,
+			unloaded: false,
+		};
+	}
+	let max_len = max_len.clamp(0.001, 65536.0);
+	let start = source.floor();
+	let mut cell = [start.x as i32, start.y as i32, start.z as i32];
+	let mut previous = cell;
+	let cell_pos = |cell: [i32; 3]| ivec3(cell[0], cell[1], cell[2]);
+
+	if let Some((hit, voxel)) = stop_at(start) {
+		return RayHit {
+			hit,
+			pos: source,
+			normal: Vec3::ZERO,
+			voxel,
+			distance: 0.0,
+			voxel_pos: start,
+			previous_pos: start,
+			unloaded: !hit,
+		};
+	}
+
+	let mut step = [0; 3];
+	// distance along the ray to the next voxel boundary on each axis
+	let mut next = [f32::INFINITY; 3];
+	// distance along the ray between voxel boundaries on each axis
+	let mut delta = [f32::INFINITY; 3];
+	for axis in 0..3 {
+		let d = dir.get(axis);
+		if d.is_nan() || d == 0.0 {
+			continue;
+		}
+		let offset = source.get(axis) - start.get(axis);
+		delta[axis] = 1.0 / d.abs();
+		if d > 0.0 {
+			step[axis] = 1;
+			next[axis] = (1.0 - offset) * delta[axis];
+		}
+		else {
+			step[axis] = -1;
+			next[axis] = offset * delta[axis];
+		}
+	}
+
+	loop {
+		// ties go to the lowest axis, so every voxel is entered through a face
+		let mut axis = 0;
+		for a in 1..3 {
+			if next[a] < next[axis] {
+				axis = a;
+			}
+		}
+		let distance = next[axis];
+		if distance > max_len {
+			break;
+		}
+		previous = cell;
+		cell[axis] += step[axis];
+		next[axis] += delta[axis];
+
+		if let Some((hit, voxel)) = stop_at(cell_pos(cell)) {
+			return RayHit {
+				hit,
+				pos: (source + dir * distance).with(axis, (cell[axis] - step[axis].min(0)) as f32),
+				normal: Vec3::axis(axis) * -step[axis] as f32,
+				voxel,
+				distance,
+				voxel_pos: cell_pos(cell),
+				previous_pos: cell_pos(previous),
+				unloaded: !hit,
+			};
+		}
+	}
+
+	RayHit {
+		hit: false,
+		pos: source + dir * max_len,
+		normal: Vec3::ZERO,
+		voxel: EMPTY,
+		distance: max_len,
+		voxel_pos: cell_pos(cell),
+		previous_pos: cell_pos(previous),
+		unloaded: false,
+	}
+}
+
+
+/// a batch of rays for a RayPool thread, it borrows from the cast_rays call that sent it
+/// the borrow is made 'static to send it to a worker, which is only sound because cast_rays does not return
+/// or unwind before every job it sent is done, see PendingJobs
+type RayJob = Box<dyn FnOnce() + Send + 'static>;
+
+/// the jobs a cast_rays call sent, each holds a clone of `done` until it has finished or panicked
+/// dropping this, also while unwinding, blocks until all of them are done
+struct PendingJobs {
+	done: Option<Sender<(usize, Vec<RayHit>)>>,
+	results: Receiver<(usize, Vec<RayHit>)>,
+}
+
+impl PendingJobs {
+	/// waits for every job, only the ones that did not panic have a result
+	fn wait(&mut self) -> Vec<(usize, Vec<RayHit>)> {
+		self.done = None;
+		self.results.iter().collect()
+	}
+}
+
+impl Drop for PendingJobs {
+	fn drop(&mut self) {
+		self.wait();
+	}
+}
+
+/// threads that cast_rays splits rays over, kept between calls so no threads are started for each one
+/// the thread calling cast_rays casts a batch too
+pub struct RayPool {
+	jobs: Option<Sender<RayJob>>,
+	workers: Vec<JoinHandle<()>>,
+}
+
+impl RayPool {
+	/// casts on up to `threads` threads, including the one calling cast_rays; 0 means one per cpu core
+	pub fn new(threads: usize) -> Self {
+		let threads = if threads == 0 {
+			thread::available_parallelism().map_or(1, |n| n.get())
+		} else {
+			threads
+		};
+		let (jobs, receiver) = mpsc::channel::<RayJob>();
+		let receiver = Arc::new(Mutex::new(receiver));
+		let workers = (1..threads)
+			.map(|i| {
+				let receiver = receiver.clone();
+				thread::Builder::new()
+					.name(format!("ray worker {}", i))
+					.spawn(move || loop {
+						let job = receiver.lock().unwrap().recv();
+						match job {
+							// a panic is reported by the cast_rays call the job came from
+							Ok(job) => panic::catch_unwind(AssertUnwindSafe(job)).unwrap_or(()),
+							Err(_) => break,
+						}
+					})
+					.unwrap()
+			})
+			.collect();
+		Self { jobs: Some(jobs), workers }
+	}
+
+	pub fn threads(&self) -> usize {
+		self.workers.len() + 1
+	}
+
+	/// casts many rays, see cast_ray_filtered
+	/// `rays` are (source, dir) pairs, and the results are in the same order
+	pub fn cast_rays(&self, rays: &[(Vec3, Vec3)], max_len: f32, filter: &RayFilter, get_voxel: impl Fn(Vec3) -> Option<Voxel> + Sync) -> Vec<RayHit> {
+		// with fewer rays per batch, handing them to another thread takes longer than casting them
+		const MIN_BATCH: usize = 32;
+		let cast = |&(source, dir): &(Vec3, Vec3)| cast_ray_filtered(source, dir, max_len, filter, &get_voxel);
+		let batches = self.threads().min(rays.len().div_ceil(MIN_BATCH));
+		if batches <= 1 {
+			return rays.iter().map(cast).collect();
+		}
+		let batch_size = rays.len().div_ceil(batches);
+		let (first, rest) = rays.split_at(batch_size);
+
+		let (done, results) = mpsc::channel();
+		let mut pending = PendingJobs { done: Some(done), results };
+		let mut sent = 0;
+		for (i, batch) in rest.chunks(batch_size).enumerate() {
+			let done = pending.done.clone().unwrap();
+			let cast = &cast;
+			let job: Box<dyn FnOnce() + Send + '_> = Box::new(move || {
+				done.send((i, batch.iter().map(cast).collect::<Vec<_>>())).ok();
+			});
+			// SAFETY: the job only borrows from this call, and `pending` waits until it has finished or panicked
+			// before this call returns or unwinds, see RayJob
+			let job: RayJob = unsafe { std::mem::transmute::<Box<dyn FnOnce() + Send + '_>, RayJob>(job) };
+			self.jobs.as_ref().unwrap().send(job).unwrap();
+			sent += 1;
+		}
+
+		let mut hits: Vec<RayHit> = first.iter().map(cast).collect();
+		let mut batches = pending.wait();
+		// every job is done, so nothing borrows from this call anymore
+		assert_eq!(batches.len(), sent, "a ray worker panicked");
+		batches.sort_unstable_by_key(|(i, _)| *i);
+		hits.extend(batches.into_iter().flat_map(|(_, batch)| batch));
+		hits
+	}
+}
+
+impl Drop for RayPool {
+	fn drop(&mut self) {
+		// closing the channel stops the workers
+		self.jobs = None;
+		for worker in self.workers.drain(..) {
+			worker.join().ok();
+		}
+	}
+}
+
+/// the chunks a ray goes through, in order
+/// the ray is scaled down to chunks, which rounds differently, so it can miss chunks the ray only clips near an edge or corner
+/// see chunks_near_rays for every chunk cast_rays may need
+/// max_len is clamped like in cast_ray
+pub fn ray_chunks(source: Vec3, dir: Vec3, max_len: f32) -> Vec<ChunkLoc> {
+	let mut locs = Vec::new();
+	let max_len = max_len.clamp(0.001, 65536.0);
+	// the same traversal, with every voxel the size of a chunk
+	cast_ray(source / WIDTH_F, dir, max_len / WIDTH_F, |locv| {
+		locs.push(locv_to_loc(locv));
+		EMPTY
+	});
+	locs
+}
+
+/// every chunk any of the rays can go through, eg. to know which ones cast_rays needs
+/// the chunks ray_chunks finds and all of their face, edge and corner neighbours, which cover what it misses
+pub fn chunks_near_rays(rays: &[(Vec3, Vec3)], max_len: f32) -> HashSet<ChunkLoc> {
+	let mut along = HashSet::new();
+	for &(source, dir) in rays {
+		along.extend(ray_chunks(source, dir, max_len));
+	}
+	let mut near = HashSet::with_capacity(along.len() * 8);
+	for loc in along {
+		for x in -1..=1 {
+			for y in -1..=1 {
+				for z in -1..=1 {
+					near.insert(loc_add(loc, (x, y, z)));
+				}
+			}
+		}
+	}
+	near
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// a world with one voxel of type 1 at (5, 0, 0)
+	fn single_voxel(pos: Vec3) -> Voxel {
+		if pos.floor() == ivec3(5, 0, 0) { 1 } else { EMPTY }
+	}
+
+	#[test]
+	fn hits_along_an_axis() {
+		let ray = cast_ray(Vec3::new(0.5, 0.5, 0.5), ivec3(1, 0, 0), 10.0, single_voxel);
+		assert!(ray.hit);
+		assert_eq!(ray.voxel, 1);
+		assert_eq!(ray.normal, ivec3(-1, 0, 0));
+		assert_eq!(ray.distance, 4.5);
+		assert_eq!(ray.pos, Vec3::new(5.0, 0.5, 0.5));
+		assert_eq!(ray.voxel_pos, ivec3(5, 0, 0));
+		assert_eq!(ray.previous_pos, ivec3(4, 0, 0));
+	}
+
+	#[test]
+	fn hits_from_above() {
+		let ray = cast_ray(Vec3::new(5.5, 4.2, 0.5), Vec3::new(0.1, -1.0, 0.0), 10.0, single_voxel);
+		assert!(ray.hit);
+		assert_eq!(ray.normal, ivec3(0, 1, 0));
+		assert_eq!(ray.pos.y, 1.0);
+		assert_eq!(ray.previous_pos, ivec3(5, 1, 0));
+	}
+
+	#[test]
+	fn misses_at_max_len() {
+		let ray = cast_ray(Vec3::new(0.5, 0.5, 0.5), ivec3(1, 0, 0), 3.0, single_voxel);
+		assert!(!ray.hit);
+		assert_eq!(ray.voxel, EMPTY);
+		assert_eq!(ray.distance, 3.0);
+		assert!((ray.pos.x - 3.5).abs() < 0.001);
+		assert_eq!(ray.voxel_pos, ivec3(3, 0, 0));
+
+		let ray = cast_ray(Vec3::new(0.5, 0.5, 0.5), ivec3(0, 1, 1), 100.0, single_voxel);
+		assert!(!ray.hit);
+	}
+
+	#[test]
+	fn ignores_invalid_rays() {
+		let origin = Vec3::new(0.5, 0.5, 0.5);
+		let x = ivec3(1, 0, 0);
+		let far = Vec3::new(3.0e9, 0.5, 0.5);
+		for (source, dir, max_len) in [
+			(origin, x, f32::NAN),
+			(origin, x, f32::INFINITY),
+			(origin, Vec3::ZERO, 10.0),
+			(origin, Vec3::new(f32::NAN, 1.0, 0.0), 10.0),
+			(Vec3::new(f32::INFINITY, 0.5, 0.5), x, 10.0),
+			(far, x, 10.0),
+			(-far, -x, 10.0),
+		] {
+			let ray = cast_ray(source, dir, max_len, |_| panic!("visited a voxel"));
+			assert!(!ray.hit);
+			assert_eq!(ray.distance, 0.0);
+		}
+	}
+
+	#[test]
+	fn does_not_skip_corners() {
+		// passes diagonally through the corner of the voxel
+		let ray = cast_ray(Vec3::new(3.01, 2.0, 0.5), Vec3::new(1.0, -1.0, 0.0), 10.0, single_voxel);
+		assert!(ray.hit);
+		assert_eq!(ray.voxel, 1);
+	}
+
+	#[test]
+	fn starts_inside_a_voxel() {
+		let ray = cast_ray(Vec3::new(5.2, 0.3, 0.9), ivec3(0, 1, 0), 10.0, single_voxel);
+		assert!(ray.hit);
+		assert_eq!(ray.distance, 0.0);
+		assert_eq!(ray.normal, Vec3::ZERO);
+		assert_eq!(ray.voxel_pos, ivec3(5, 0, 0));
+	}
+
+	#[test]
+	fn hits_at_grazing_angles() {
+		// only reaches y = 1 after travelling about 5 voxels along x
+		let ray = cast_ray(Vec3::new(0.5, 0.9999, 0.5), Vec3::new(1.0, 0.00002, 0.0), 10.0, |pos| {
+			if pos == ivec3(5, 1, 0) { 1 } else { EMPTY }
+		});
+		assert!(ray.hit);
+		assert_eq!(ray.normal, ivec3(0, -1, 0));
+		assert_eq!(ray.previous_pos, ivec3(5, 0, 0));
+		assert_eq!(ray.pos.y, 1.0);
+	}
+
+	#[test]
+	fn visits_every_voxel_through_a_face() {
+		let dirs = [
+			Vec3::new(1.0, 1.0, 1.0),
+			Vec3::new(-1.0, 0.3, 0.7),
+			Vec3::new(0.2, -1.0, -0.01),
+			Vec3::new(-0.5, -0.5, 0.0),
+			Vec3::new(1.0, 0.0, -1.0),
+		];
+		for source in [Vec3::new(0.5, 0.5, 0.5), Vec3::new(-31.99, 32.0, 0.0), Vec3::new(31.999, -0.001, 64.0)] {
+			for dir in dirs {
+				let mut visited = Vec::new();
+				cast_ray(source, dir, 100.0, |pos| {
+					visited.push(pos);
+					EMPTY
+				});
+				assert_eq!(visited[0], source.floor());
+				for pair in visited.windows(2) {
+					let step = (pair[1] - pair[0]).abs();
+					assert_eq!(step.x + step.y + step.z, 1.0, "{:?} skipped from {:?} to {:?}", dir, pair[0], pair[1]);
+				}
+				// the last voxel is close to the end of the ray
+				let end = source + dir.normalized() * 100.0;
+				assert!((*visited.last().unwrap() + Vec3::ONE * 0.5).distance_squared_to(end) < 3.0);
+			}
+		}
+	}
+
+	/// glass, a plant, stone and dirt along the x axis
+	fn row(pos: Vec3) -> Option<Voxel> {
+		use crate::types::test_types::*;
+		if pos.y != 0.0 || pos.z != 0.0 {
+			return Some(EMPTY);
+		}
+		Some(match pos.x as i32 {
+			2 => GLASS,
+			3 => PLANT,
+			4 => 1,
+			5 => 2,
+			_ => EMPTY,
+		})
+	}
+
+	#[test]
+	fn filters_voxel_types() {
+		crate::types::test_types::init();
+		let source = Vec3::new(0.5, 0.5, 0.5);
+		let dir = ivec3(1, 0, 0);
+		let cast = |filter: RayFilter| cast_ray_filtered(source, dir, 10.0, &filter, row);
+
+		let ray = cast(RayFilter::default());
+		assert_eq!(ray.voxel_pos, ivec3(2, 0, 0));
+
+		let ray = cast(RayFilter { ignore_transparent: true, ..Default::default() });
+		assert_eq!(ray.voxel, 1);
+		assert_eq!(ray.previous_pos, ivec3(3, 0, 0));
+
+		let ray = cast(RayFilter {
+			ignore_unselectable: true,
+			ignore: vec![crate::types::test_types::GLASS],
+			..Default::default()
+		});
+		assert_eq!(ray.voxel, 1);
+
+		let ray = cast(RayFilter { hit_only: vec![2], ..Default::default() });
+		assert!(ray.hit);
+		assert_eq!(ray.voxel_pos, ivec3(5, 0, 0));
+	}
+
+	#[test]
+	fn stops_at_unloaded_voxels() {
+		let unloaded_past_3 = |pos: Vec3| if pos.x >= 3.0 { None } else { Some(EMPTY) };
+		let source = Vec3::new(0.5, 0.5, 0.5);
+		let dir = ivec3(1, 0, 0);
+
+		let ray = cast_ray_filtered(source, dir, 10.0, &RayFilter::default(), unloaded_past_3);
+		assert!(!ray.hit);
+		assert!(!ray.unloaded);
+		assert_eq!(ray.distance, 10.0);
+
+		let filter = RayFilter { stop_at_unloaded: true, ..Default::default() };
+		let ray = cast_ray_filtered(source, dir, 10.0, &filter, unloaded_past_3);
+		assert!(!ray.hit);
+		assert!(ray.unloaded);
+		assert_eq!(ray.voxel, EMPTY);
+		assert_eq!(ray.voxel_pos, ivec3(3, 0, 0));
+		assert_eq!(ray.pos, Vec3::new(3.0, 0.5, 0.5));
+	}
+
+	#[test]
+	fn casts_batches_like_single_rays() {
+		let rays: Vec<(Vec3, Vec3)> = (0..200)
+			.map(|i| {
+				let i = i as f32;
+				(Vec3::new(0.5, i * 0.05, -3.0 + i * 0.03), Vec3::new(1.0, (i * 0.7).sin(), (i * 1.3).cos()))
+			})
+			.collect();
+		let world = |pos: Vec3| Some(if pos.x >= 4.0 && pos.y < 2.0 { 1 } else { EMPTY });
+		let filter = RayFilter::default();
+		let batched = RayPool::new(4).cast_rays(&rays, 20.0, &filter, world);
+		let single: Vec<RayHit> = rays.iter()
+			.map(|&(source, dir)| cast_ray_filtered(source, dir, 20.0, &filter, world))
+			.collect();
+		assert_eq!(batched, single);
+		assert!(batched.iter().any(|r| r.hit));
+		assert!(batched.iter().any(|r| !r.hit));
+	}
+
+	#[test]
+	fn pool_survives_panicking_rays() {
+		let pool = RayPool::new(4);
+		let rays = vec![(Vec3::new(0.5, 0.5, 0.5), ivec3(1, 0, 0)); 200];
+		let cast = panic::catch_unwind(AssertUnwindSafe(|| {
+			pool.cast_rays(&rays, 10.0, &RayFilter::default(), |_| panic!("no voxels here"))
+		}));
+		assert!(cast.is_err());
+		let hits = pool.cast_rays(&rays, 10.0, &RayFilter::default(), |pos| Some(if pos.x >= 5.0 { 1 } else { EMPTY }));
+		assert!(hits.iter().all(|r| r.hit && r.distance == 4.5));
+	}
+
+	#[test]
+	fn waits_for_every_batch_when_one_panics() {
+		use std::sync::atomic::{AtomicUsize, Ordering};
+		use std::time::Duration;
+		let pool = RayPool::new(4);
+		// four batches of 32, every ray in the last one panics
+		let mut rays = vec![(Vec3::new(0.5, 0.5, 0.5), ivec3(1, 0, 0)); 96];
+		rays.extend(vec![(Vec3::new(0.5, 1.5, 0.5), ivec3(1, 0, 0)); 32]);
+		let cast = AtomicUsize::new(0);
+		let result = panic::catch_unwind(AssertUnwindSafe(|| {
+			pool.cast_rays(&rays, 10.0, &RayFilter::default(), |pos| {
+				assert!(pos.y < 1.0, "no voxels up here");
+				thread::sleep(Duration::from_millis(1));
+				cast.fetch_add(1, Ordering::SeqCst);
+				Some(1)
+			})
+		}));
+		assert!(result.is_err());
+		assert_eq!(cast.load(Ordering::SeqCst), 96);
+	}
+
+	#[test]
+	fn finds_the_chunks_of_a_ray() {
+		let locs = ray_chunks(Vec3::new(1.0, 1.0, 1.0), ivec3(1, 0, 0), 70.0);
+		assert_eq!(locs, vec![(0, 0, 0), (1, 0, 0), (2, 0, 0)]);
+
+		let locs = ray_chunks(Vec3::new(1.0, 31.0, 1.0), Vec3::new(-1.0, 1.0, 0.0), 10.0);
+		assert_eq!(locs, vec![(0, 0, 0), (-1, 0, 0), (-1, 1, 0)]);
+	}
+
+	#[test]
+	fn finds_chunks_clipped_at_corners() {
+		// nearly diagonal rays from close to chunk corners, where ray_chunks rounds differently from cast_ray
+		let mut seed = 12345_u32;
+		let mut random = move || {
+			seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+			(seed >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0
+		};
+		let rays: Vec<(Vec3, Vec3)> = (0..2000)
+			.map(|_| {
+				let mut corner = || (random() * 2.0).round() * WIDTH_F + random() * 0.01;
+				let source = Vec3::new(corner(), corner(), corner());
+				let mut axis = || random().signum() * (1.0 + random() * 0.0001);
+				(source, Vec3::new(axis(), axis(), axis()))
+			})
+			.collect();
+		for &(source, dir) in &rays {
+			let near = chunks_near_rays(&[(source, dir)], 200.0);
+			cast_ray(source, dir, 200.0, |pos| {
+				assert!(near.contains(&wpos_to_loc(pos)), "{:?} from {:?} along {:?}", pos, source, dir);
+				EMPTY
+			});
+		}
+	}
+}
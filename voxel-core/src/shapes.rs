@@ -0,0 +1,252 @@
+use crate::common::*;
+use crate::math::{Vec2, Vec3};
+use crate::mesh::{FACE_VERTS, NORMALS, QUAD_OFFSETS, face_uv};
+
+
+/// geometry of a voxel type
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Shape {
+	/// a full cube, the only shape that can be opaque and greedy meshed
+	Cube,
+	/// lower half of a cube
+	Slab,
+	/// a slab with a quarter cube on top, rising towards +z
+	Stair,
+	/// two crossed planes, for plants
+	Cross,
+	/// triangles from a mesh resource
+	Custom,
+}
+
+/// two triangles of a voxel model, in voxel space where the voxel spans 0..1 on all axes
+#[derive(Clone, Copy)]
+pub struct ModelQuad {
+	pub verts: [Vec3; 6],
+	pub normals: [Vec3; 6],
+	pub uvs: [Vec2; 6],
+	/// the side of the voxel the quad lies on, if any; it is culled like the cube face on that side
+	pub cull: Option<usize>,
+	/// the face whose texture layer and tangent are used
+	pub face: usize,
+}
+
+/// axis aligned boxes the box-like shapes are made of, as (min, max)
+const SLAB_BOXES: [(Vec3, Vec3); 1] = [
+	(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.5, 1.0))];
+const STAIR_BOXES: [(Vec3, Vec3); 2] = [
+	(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.5, 1.0)),
+	(Vec3::new(0.0, 0.5, 0.5), Vec3::new(1.0, 1.0, 1.0))];
+
+/// the two crossed planes of a cross, as corners in the same order as FACE_VERTS, seen from the front
+/// each plane is listed from both sides so it can be seen from behind
+const CROSS_PLANES: [[Vec3; 4]; 4] = [
+	[ivec3(1, 1, 1), ivec3(0, 1, 0), ivec3(0, 0, 0), ivec3(1, 0, 1)],
+	[ivec3(0, 1, 0), ivec3(1, 1, 1), ivec3(1, 0, 1), ivec3(0, 0, 0)],
+	[ivec3(0, 1, 1), ivec3(1, 1, 0), ivec3(1, 0, 0), ivec3(0, 0, 1)],
+	[ivec3(1, 1, 0), ivec3(0, 1, 1), ivec3(0, 0, 1), ivec3(1, 0, 0)]];
+const CROSS_UVS: [Vec2; 4] = [
+	Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0),
+	Vec2::new(1.0, 1.0), Vec2::new(0.0, 1.0)];
+
+
+impl Shape {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"cube" => Some(Self::Cube),
+			"slab" => Some(Self::Slab),
+			"stair" => Some(Self::Stair),
+			"cross" => Some(Self::Cross),
+			"custom" => Some(Self::Custom),
+			_ => None,
+		}
+	}
+}
+
+/// build the model of a shape, turned `rotation` quarter turns around the y axis
+/// None for cubes, which are meshed by the meshers directly
+/// custom shapes take their quads from `custom`, see model_from_triangles
+pub fn build_model(shape: Shape, rotation: u8, custom: Option<Vec<ModelQuad>>) -> Option<Vec<ModelQuad>> {
+	match shape {
+		Shape::Cube => None,
+		Shape::Slab => Some(box_model(&SLAB_BOXES, rotation)),
+		Shape::Stair => Some(box_model(&STAIR_BOXES, rotation)),
+		Shape::Cross => Some(rotate_model(cross_model(), rotation)),
+		Shape::Custom => Some(rotate_model(custom?, rotation)),
+	}
+}
+
+/// faces of a set of boxes, without faces that are covered by another box
+/// rotated before the uvs are taken from the position like on cubes, so textures stay upright and line up with neighbouring voxels
+fn box_model(boxes: &[(Vec3, Vec3)], rotation: u8) -> Vec<ModelQuad> {
+	let mut model = Vec::new();
+	for (i, &(min, max)) in boxes.iter().enumerate() {
+		for (face, verts) in FACE_VERTS.iter().enumerate() {
+			let corners = verts.map(|c| min + c * (max - min));
+			let covered = boxes.iter().enumerate().any(|(j, &(other_min, other_max))| {
+				j != i && corners.iter().all(|&c| {
+					let touching = if face.is_multiple_of(2) { other_min } else { other_max };
+					let axis = face / 2;
+					c.get(axis) == touching.get(axis)
+						&& (0..3).all(|a| c.get(a) >= other_min.get(a) && c.get(a) <= other_max.get(a))
+				})
+			});
+			if covered {
+				continue;
+			}
+			let mut face = face;
+			let mut corners = corners;
+			for _ in 0..rotation {
+				corners = corners.map(rotate_pos);
+				face = rotate_face(face);
+			}
+			let on_side = if face.is_multiple_of(2) { 1.0 } else { 0.0 };
+			let cull = corners.iter().all(|&c| c.get(face / 2) == on_side).then_some(face);
+			model.push(ModelQuad {
+				verts: QUAD_OFFSETS.map(|i| corners[i]),
+				normals: [NORMALS[face]; 6],
+				uvs: QUAD_OFFSETS.map(|i| face_uv(corners[i], face)),
+				cull,
+				face,
+			});
+		}
+	}
+	model
+}
+
+fn cross_model() -> Vec<ModelQuad> {
+	CROSS_PLANES.iter().map(|corners| {
+		let normal = (corners[3] - corners[0]).cross(corners[1] - corners[0]).normalized();
+		ModelQuad {
+			verts: QUAD_OFFSETS.map(|i| corners[i]),
+			normals: [normal; 6],
+			uvs: QUAD_OFFSETS.map(|i| CROSS_UVS[i]),
+			cull: None,
+			face: 0,
+		}
+	}).collect()
+}
+
+/// triangles of a mesh, paired up into quads
+/// an odd triangle out is paired with a degenerate one
+/// normals and uvs may be empty, indices may be empty if the vertices are not indexed
+pub fn model_from_triangles(verts: &[Vec3], normals: &[Vec3], uvs: &[Vec2], indices: &[usize]) -> Vec<ModelQuad> {
+	let indices: Vec<usize> = if indices.is_empty() {
+		(0..verts.len()).collect()
+	} else {
+		indices.to_vec()
+	};
+
+	let mut model = Vec::new();
+	for quad in indices.chunks(6) {
+		let mut quad = quad.to_vec();
+		while quad.len() < 6 {
+			quad.push(quad[quad.len() - 1]);
+		}
+		let vert = |i: usize| verts[quad[i]];
+		let normal = |i: usize| normals.get(quad[i]).copied().unwrap_or(Vec3::UP);
+		let uv = |i: usize| uvs.get(quad[i]).copied().unwrap_or(Vec2::ZERO);
+		// front faces are clockwise
+		let face_normal = (vert(2) - vert(0)).cross(vert(1) - vert(0));
+		model.push(ModelQuad {
+			verts: [vert(0), vert(1), vert(2), vert(3), vert(4), vert(5)],
+			normals: [normal(0), normal(1), normal(2), normal(3), normal(4), normal(5)],
+			uvs: [uv(0), uv(1), uv(2), uv(3), uv(4), uv(5)],
+			cull: None,
+			face: nearest_face(face_normal),
+		});
+	}
+	model
+}
+
+fn rotate_model(mut model: Vec<ModelQuad>, rotation: u8) -> Vec<ModelQuad> {
+	for quad in model.iter_mut() {
+		for _ in 0..rotation {
+			quad.verts = quad.verts.map(rotate_pos);
+			quad.normals = quad.normals.map(rotate_dir);
+			quad.face = rotate_face(quad.face);
+			quad.cull = quad.cull.map(rotate_face);
+		}
+	}
+	model
+}
+
+/// a quarter turn around the vertical center line of the voxel
+#[inline]
+fn rotate_pos(pos: Vec3) -> Vec3 {
+	Vec3::new(1.0 - pos.z, pos.y, pos.x)
+}
+
+#[inline]
+fn rotate_dir(dir: Vec3) -> Vec3 {
+	Vec3::new(-dir.z, dir.y, dir.x)
+}
+
+#[inline]
+fn rotate_face(face: usize) -> usize {
+	nearest_face(rotate_dir(NORMALS[face]))
+}
+
+/// the face whose normal is closest to a direction
+fn nearest_face(dir: Vec3) -> usize {
+	(0..6).max_by(|&a, &b| NORMALS[a].dot(dir).partial_cmp(&NORMALS[b].dot(dir)).unwrap()).unwrap()
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn slab_has_a_culled_bottom_and_sides() {
+		let model = build_model(Shape::Slab, 0, None).unwrap();
+		assert_eq!(model.len(), 6);
+		let culled: Vec<Option<usize>> = model.iter().map(|q| q.cull).collect();
+		// the top is half way up, so it is never culled
+		assert_eq!(culled.iter().filter(|c| c.is_none()).count(), 1);
+		assert!(culled.contains(&Some(3)));
+	}
+
+	#[test]
+	fn stair_skips_covered_faces() {
+		let model = build_model(Shape::Stair, 0, None).unwrap();
+		// the bottom of the upper box lies on the lower box, the half covered top of the lower box is kept
+		assert_eq!(model.len(), 11);
+		assert!(model.iter().all(|q| q.verts.iter().all(|v| (0.0..=1.0).contains(&v.y))));
+	}
+
+	#[test]
+	fn rotation_turns_faces() {
+		let stair = build_model(Shape::Stair, 0, None).unwrap();
+		let turned = build_model(Shape::Stair, 1, None).unwrap();
+		let back_faces = |model: &[ModelQuad], face| model.iter().filter(|q| q.cull == Some(face)).count();
+		assert_eq!(back_faces(&stair, 4), back_faces(&turned, rotate_face(4)));
+		assert_ne!(rotate_face(4), 4);
+		let mut face = 0;
+		for _ in 0..4 {
+			face = rotate_face(face);
+		}
+		assert_eq!(face, 0);
+	}
+
+	#[test]
+	fn cubes_have_no_model() {
+		assert!(build_model(Shape::Cube, 0, None).is_none());
+		assert!(build_model(Shape::Custom, 0, None).is_none());
+		assert_eq!(build_model(Shape::Cross, 2, None).unwrap().len(), 4);
+	}
+
+	#[test]
+	fn triangles_are_paired_into_quads() {
+		let verts = [ivec3(0, 1, 0), ivec3(1, 1, 0), ivec3(1, 1, 1)];
+		let model = model_from_triangles(&verts, &[], &[], &[]);
+		assert_eq!(model.len(), 1);
+		assert_eq!(model[0].verts[3], verts[2]);
+		assert_eq!(model[0].normals[0], Vec3::UP);
+		assert_eq!(model[0].face, 2);
+
+		let model = model_from_triangles(&verts, &[], &[], &[0, 1, 2, 2, 1, 0, 0, 1, 2]);
+		assert_eq!(model.len(), 2);
+		assert_eq!(model[0].face, 2);
+		assert_eq!(model[1].face, 2);
+	}
+}
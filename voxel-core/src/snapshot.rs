@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use crate::chunk::ChunkCore;
+use crate::common::*;
+use crate::math::Vec3;
+
+
+/// some of the chunks of a world, borrowed to be read from other threads, eg. to cast rays
+#[derive(Clone, Default)]
+pub struct WorldSnapshot<'a> {
+	/// None for chunks that are loaded but only contain air
+	chunks: HashMap<ChunkLoc, Option<&'a ChunkCore>>,
+}
+
+
+impl<'a> WorldSnapshot<'a> {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn insert(&mut self, loc: ChunkLoc, core: Option<&'a ChunkCore>) {
+		self.chunks.insert(loc, core);
+	}
+
+	pub fn contains(&self, loc: ChunkLoc) -> bool {
+		self.chunks.contains_key(&loc)
+	}
+
+	/// None if the chunk is not in the snapshot
+	pub fn get_voxel(&self, wpos: Vec3) -> Option<Voxel> {
+		match self.chunks.get(&wpos_to_loc(wpos))? {
+			Some(core) => Some(core.get_voxel(wpos_to_vposv(wpos))),
+			None => Some(EMPTY),
+		}
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn looks_up_voxels_by_world_position() {
+		let mut core = ChunkCore::new();
+		core.set_voxel(ivec3(31, 0, 5), 3);
+		let mut snapshot = WorldSnapshot::new();
+		snapshot.insert((-1, 0, 0), Some(&core));
+		snapshot.insert((0, 0, 0), None);
+
+		assert_eq!(snapshot.get_voxel(Vec3::new(-0.5, 0.5, 5.5)), Some(3));
+		assert_eq!(snapshot.get_voxel(Vec3::new(-1.5, 0.5, 5.5)), Some(EMPTY));
+		assert_eq!(snapshot.get_voxel(Vec3::new(0.5, 0.5, 5.5)), Some(EMPTY));
+		assert_eq!(snapshot.get_voxel(Vec3::new(0.5, 32.5, 5.5)), None);
+		assert!(!snapshot.contains((1, 0, 0)));
+	}
+
+	#[test]
+	fn rays_can_share_a_snapshot() {
+		use crate::raycast::{RayFilter, RayPool};
+		let core = ChunkCore::new_filled(1);
+		let mut snapshot = WorldSnapshot::new();
+		snapshot.insert((0, 0, 0), Some(&core));
+		let pool = RayPool::new(4);
+		let rays = vec![(Vec3::new(-10.5, 0.5, 0.5), ivec3(1, 0, 0)); 100];
+		let filter = RayFilter { stop_at_unloaded: true, ..Default::default() };
+		let hits = pool.cast_rays(&rays, 20.0, &filter, |pos| snapshot.get_voxel(pos));
+		assert!(hits.iter().all(|r| r.unloaded && r.voxel_pos == ivec3(-11, 0, 0)));
+
+		let hits = pool.cast_rays(&rays, 20.0, &RayFilter::default(), |pos| snapshot.get_voxel(pos));
+		assert!(hits.iter().all(|r| r.hit && r.voxel_pos == ivec3(0, 0, 0)));
+	}
+}
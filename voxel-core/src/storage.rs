@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::chunk::ChunkCore;
+use crate::common::*;
+
+/// width of a region in chunks
+const REGION_WIDTH: i32 = 8;
+const REGION_VOLUME: usize = (REGION_WIDTH * REGION_WIDTH * REGION_WIDTH) as usize;
+/// the first bytes of every region file
+const MAGIC: [u8; 4] = *b"GDVR";
+/// the region format written by this version, files with a newer one are not read or written
+/// 1: voxels are (run length: u16, voxel: u8) runs, see ChunkCore::deserialize_u8
+/// 2: voxels are (run length: u16, voxel: u16) runs
+const VERSION: u32 = 2;
+/// the magic followed by the version as a u32
+const PREFIX_SIZE: u64 = 8;
+/// each header entry is a u32 offset followed by a u32 length
+const ENTRY_SIZE: u64 = 8;
+const HEADER_SIZE: u64 = PREFIX_SIZE + REGION_VOLUME as u64 * ENTRY_SIZE;
+
+type RegionLoc = (i32, i32, i32);
+
+#[derive(Clone)]
+struct Header {
+	/// older regions are read as they are, and upgraded to VERSION when they are written
+	version: u32,
+	entries: Vec<(u32, u32)>,
+}
+
+/// the header of a region, None if its file does not exist yet,
+/// or why the file could not be used, it is then never written to so the chunks in it are not lost
+type CachedHeader = Result<Option<Header>, String>;
+
+/// Reads and writes chunks to region files, each holding 8x8x8 chunks.
+/// A region file starts with the magic and the format version, then a header of (offset, length) pairs,
+/// one per chunk, followed by the chunk data.
+/// An offset of 0 means the chunk has not been saved.
+/// Files are rewritten to a temporary file which replaces the old one, so they are never left half written.
+pub struct ChunkStorage {
+	dir: Option<PathBuf>,
+	/// headers are read once per region, including regions without a file, so has and load do not hit the disk for every chunk
+	headers: HashMap<RegionLoc, CachedHeader>,
+}
+
+
+impl ChunkStorage {
+	pub fn new() -> Self {
+		Self {
+			dir: None,
+			headers: HashMap::new(),
+		}
+	}
+
+	/// set the directory regions are saved to, creating it if needed
+	pub fn set_dir(&mut self, dir: PathBuf) -> io::Result<()> {
+		fs::create_dir_all(&dir)?;
+		self.dir = Some(dir);
+		self.headers.clear();
+		Ok(())
+	}
+
+	/// false for chunks in regions that can not be read, load returns the error for those
+	pub fn has(&mut self, loc: ChunkLoc) -> bool {
+		let (region, index) = region_of(loc);
+		match self.header(region) {
+			Ok(Some(header)) => header.entries[index].0 != 0,
+			_ => false,
+		}
+	}
+
+	/// Ok(None) if the chunk has not been saved
+	pub fn load(&mut self, loc: ChunkLoc) -> io::Result<Option<ChunkCore>> {
+		let (region, index) = region_of(loc);
+		let (version, (offset, len)) = match self.header(region)? {
+			Some(header) => (header.version, header.entries[index]),
+			None => return Ok(None),
+		};
+		if offset == 0 {
+			return Ok(None);
+		}
+		let mut file = File::open(self.region_path(region).unwrap())?;
+		let mut data = vec![0; len as usize];
+		file.seek(SeekFrom::Start(offset as u64))?;
+		file.read_exact(&mut data)?;
+		deserialize(version, &data)
+			.map(Some)
+			.ok_or_else(|| invalid_data(format!("chunk {:?} is corrupt", loc)))
+	}
+
+	/// rewrites the whole region the chunk is in
+	/// fails without touching the file if the region can not be read
+	pub fn save(&mut self, loc: ChunkLoc, core: &ChunkCore) -> io::Result<()> {
+		let (region, index) = region_of(loc);
+		let path = match self.region_path(region) {
+			Some(path) => path,
+			None => return Ok(()),
+		};
+		let mut chunks = match self.header(region)?.cloned() {
+			Some(header) => read_chunks(&path, &header)?,
+			None => vec![Vec::new(); REGION_VOLUME],
+		};
+		chunks[index] = core.serialize();
+		let header = write_region(&path, &chunks)?;
+		self.headers.insert(region, Ok(Some(header)));
+		Ok(())
+	}
+
+	/// get the cached header for a region, reading it from disk if needed
+	fn header(&mut self, region: RegionLoc) -> io::Result<Option<&Header>> {
+		if !self.headers.contains_key(&region) {
+			let path = match self.region_path(region) {
+				Some(path) => path,
+				None => return Ok(None),
+			};
+			let header = read_header(&path).map_err(|e| format!("region file {}: {}", path.display(), e));
+			self.headers.insert(region, header);
+		}
+		match &self.headers[&region] {
+			Ok(header) => Ok(header.as_ref()),
+			Err(e) => Err(invalid_data(e.clone())),
+		}
+	}
+
+	fn region_path(&self, region: RegionLoc) -> Option<PathBuf> {
+		let dir = self.dir.as_ref()?;
+		Some(dir.join(format!("r.{}.{}.{}.region", region.0, region.1, region.2)))
+	}
+}
+
+impl Default for ChunkStorage {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+fn invalid_data(message: String) -> io::Error {
+	io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// returns None if the file does not exist
+fn read_header(path: &Path) -> io::Result<Option<Header>> {
+	let mut file = match File::open(path) {
+		Ok(file) => file,
+		Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+		Err(e) => return Err(e),
+	};
+	let mut raw = vec![0; HEADER_SIZE as usize];
+	file.read_exact(&mut raw)?;
+	if raw[..4] != MAGIC {
+		return Err(invalid_data("not a region file".into()));
+	}
+	let version = u32::from_le_bytes([raw[4], raw[5], raw[6], raw[7]]);
+	if version == 0 || version > VERSION {
+		return Err(invalid_data(format!("unknown region format version {}", version)));
+	}
+	let entries = raw[PREFIX_SIZE as usize..]
+		.chunks_exact(ENTRY_SIZE as usize)
+		.map(|e| (
+			u32::from_le_bytes([e[0], e[1], e[2], e[3]]),
+			u32::from_le_bytes([e[4], e[5], e[6], e[7]]),
+		))
+		.collect();
+	Ok(Some(Header { version, entries }))
+}
+
+/// read chunk data saved in the given region format version
+fn deserialize(version: u32, data: &[u8]) -> Option<ChunkCore> {
+	match version {
+		1 => ChunkCore::deserialize_u8(data),
+		_ => ChunkCore::deserialize(data),
+	}
+}
+
+/// the data of every chunk in a region, empty for chunks that have not been saved
+/// chunks from older region versions are converted to the current one
+fn read_chunks(path: &Path, header: &Header) -> io::Result<Vec<Vec<u8>>> {
+	let mut raw = Vec::new();
+	File::open(path)?.read_to_end(&mut raw)?;
+	header.entries
+		.iter()
+		.map(|&(offset, len)| {
+			if offset == 0 {
+				return Ok(Vec::new());
+			}
+			let data = raw.get(offset as usize..(offset + len) as usize)
+				.ok_or_else(|| invalid_data(format!("region file {} is truncated", path.display())))?;
+			if header.version == VERSION {
+				return Ok(data.to_vec());
+			}
+			deserialize(header.version, data)
+				.map(|core| core.serialize())
+				.ok_or_else(|| invalid_data(format!("region file {} has a corrupt chunk", path.display())))
+		})
+		.collect()
+}
+
+/// writes a region to a temporary file and moves it over the old one, returns the new header
+fn write_region(path: &Path, chunks: &[Vec<u8>]) -> io::Result<Header> {
+	let mut entries = Vec::with_capacity(REGION_VOLUME);
+	let mut offset = HEADER_SIZE as u32;
+	for data in chunks {
+		if data.is_empty() {
+			entries.push((0, 0));
+		} else {
+			entries.push((offset, data.len() as u32));
+			offset += data.len() as u32;
+		}
+	}
+	let mut raw = Vec::with_capacity(offset as usize);
+	raw.extend_from_slice(&MAGIC);
+	raw.extend_from_slice(&VERSION.to_le_bytes());
+	for &(offset, len) in &entries {
+		raw.extend_from_slice(&offset.to_le_bytes());
+		raw.extend_from_slice(&len.to_le_bytes());
+	}
+	for data in chunks {
+		raw.extend_from_slice(data);
+	}
+
+	let temp = path.with_extension("region.tmp");
+	let mut file = File::create(&temp)?;
+	file.write_all(&raw)?;
+	file.sync_all()?;
+	fs::rename(&temp, path)?;
+	Ok(Header { version: VERSION, entries })
+}
+
+/// returns the region a chunk is in and its index within that region
+fn region_of(loc: ChunkLoc) -> (RegionLoc, usize) {
+	let region = (
+		loc.0.div_euclid(REGION_WIDTH),
+		loc.1.div_euclid(REGION_WIDTH),
+		loc.2.div_euclid(REGION_WIDTH),
+	);
+	let index = loc.0.rem_euclid(REGION_WIDTH) * REGION_WIDTH * REGION_WIDTH
+		+ loc.1.rem_euclid(REGION_WIDTH) * REGION_WIDTH
+		+ loc.2.rem_euclid(REGION_WIDTH);
+	(region, index as usize)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn temp_storage(name: &str) -> (ChunkStorage, PathBuf) {
+		let dir = std::env::temp_dir().join(format!("voxel-core-test-{}-{}", name, std::process::id()));
+		fs::remove_dir_all(&dir).ok();
+		let mut storage = ChunkStorage::new();
+		storage.set_dir(dir.clone()).unwrap();
+		(storage, dir)
+	}
+
+	#[test]
+	fn without_a_dir_nothing_is_saved() {
+		let mut storage = ChunkStorage::new();
+		storage.save((0, 0, 0), &ChunkCore::new_filled(1)).unwrap();
+		assert!(!storage.has((0, 0, 0)));
+		assert!(storage.load((0, 0, 0)).unwrap().is_none());
+	}
+
+	#[test]
+	fn save_and_load() {
+		let (mut storage, dir) = temp_storage("save");
+		let mut core = ChunkCore::new_filled(1);
+		core.set_voxel(ivec3(1, 2, 3), 7);
+		for loc in [(0, 0, 0), (-1, -9, 3), (8, 7, 100)] {
+			assert!(!storage.has(loc));
+			storage.save(loc, &core).unwrap();
+			assert!(storage.has(loc));
+		}
+		// a new storage reads the headers from disk
+		let mut reopened = ChunkStorage::new();
+		reopened.set_dir(dir.clone()).unwrap();
+		let loaded = reopened.load((-1, -9, 3)).unwrap().unwrap();
+		assert_eq!(loaded.get_voxel(ivec3(1, 2, 3)), 7);
+		assert_eq!(loaded.get_voxel(ivec3(3, 2, 1)), 1);
+		assert!(reopened.load((1, 0, 0)).unwrap().is_none());
+		fs::remove_dir_all(dir).ok();
+	}
+
+	#[test]
+	fn resaving_bigger_chunks_keeps_the_others() {
+		let (mut storage, dir) = temp_storage("resave");
+		storage.save((0, 0, 0), &ChunkCore::new_filled(1)).unwrap();
+		storage.save((1, 0, 0), &ChunkCore::new_filled(2)).unwrap();
+		let mut bigger = ChunkCore::new_filled(1);
+		for i in (0..VOLUME).step_by(3) {
+			bigger.set_voxel(index_to_vposv(i), 3);
+		}
+		storage.save((0, 0, 0), &bigger).unwrap();
+		assert_eq!(storage.load((0, 0, 0)).unwrap().unwrap().serialize(), bigger.serialize());
+		assert_eq!(storage.load((1, 0, 0)).unwrap().unwrap().get_voxel_i((0, 0, 0)), 2);
+		assert!(!dir.join("r.0.0.0.region.tmp").exists());
+		fs::remove_dir_all(dir).ok();
+	}
+
+	#[test]
+	fn rejects_unknown_versions() {
+		let (mut storage, dir) = temp_storage("version");
+		let path = dir.join("r.0.0.0.region");
+		let mut raw = vec![0; HEADER_SIZE as usize];
+		raw[..4].copy_from_slice(&MAGIC);
+		raw[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+		fs::write(&path, &raw).unwrap();
+
+		assert!(!storage.has((0, 0, 0)));
+		assert!(matches!(storage.load((0, 0, 0)), Err(e) if e.kind() == ErrorKind::InvalidData));
+		assert!(storage.save((0, 0, 0), &ChunkCore::new_filled(1)).is_err());
+		assert_eq!(fs::read(&path).unwrap(), raw);
+
+		fs::write(&path, b"not a region").unwrap();
+		let mut reopened = ChunkStorage::new();
+		reopened.set_dir(dir.clone()).unwrap();
+		assert!(reopened.load((0, 0, 0)).is_err());
+		fs::remove_dir_all(dir).ok();
+	}
+
+	#[test]
+	fn reads_and_upgrades_version_1() {
+		let (mut storage, dir) = temp_storage("version1");
+		// chunk (0, 0, 1) is filled with voxel 5, stored as a single (u16, u8) run
+		let mut raw = vec![0; HEADER_SIZE as usize];
+		raw[..4].copy_from_slice(&MAGIC);
+		raw[4..8].copy_from_slice(&1u32.to_le_bytes());
+		let entry = PREFIX_SIZE as usize + ENTRY_SIZE as usize;
+		raw[entry..entry + 4].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+		raw[entry + 4..entry + 8].copy_from_slice(&3u32.to_le_bytes());
+		raw.extend_from_slice(&(VOLUME as u16).to_le_bytes());
+		raw.push(5);
+		fs::write(dir.join("r.0.0.0.region"), &raw).unwrap();
+
+		assert!(storage.has((0, 0, 1)));
+		assert_eq!(storage.load((0, 0, 1)).unwrap().unwrap().get_voxel_i((3, 4, 5)), 5);
+		// saving another chunk rewrites the region in the current version
+		storage.save((0, 0, 0), &ChunkCore::new_filled(2)).unwrap();
+		let mut reopened = ChunkStorage::new();
+		reopened.set_dir(dir.clone()).unwrap();
+		assert_eq!(reopened.header((0, 0, 0)).unwrap().unwrap().version, VERSION);
+		assert_eq!(reopened.load((0, 0, 1)).unwrap().unwrap().get_voxel_i((3, 4, 5)), 5);
+		assert_eq!(reopened.load((0, 0, 0)).unwrap().unwrap().get_voxel_i((3, 4, 5)), 2);
+		fs::remove_dir_all(dir).ok();
+	}
+
+	#[test]
+	fn missing_regions_are_cached() {
+		let (mut storage, dir) = temp_storage("missing");
+		assert!(!storage.has((0, 0, 0)));
+		// a file that appears later is not looked for again
+		let mut other = ChunkStorage::new();
+		other.set_dir(dir.clone()).unwrap();
+		other.save((0, 0, 0), &ChunkCore::new_filled(1)).unwrap();
+		assert!(!storage.has((0, 0, 0)));
+		assert!(storage.load((0, 0, 0)).unwrap().is_none());
+		fs::remove_dir_all(dir).ok();
+	}
+
+	#[test]
+	fn regions_wrap_negative_locs() {
+		assert_eq!(region_of((0, 0, 0)), ((0, 0, 0), 0));
+		assert_eq!(region_of((-1, 0, 0)), ((-1, 0, 0), 7 * 64));
+		assert_eq!(region_of((9, -8, 2)), ((1, -1, 0), 64 + 2));
+	}
+}
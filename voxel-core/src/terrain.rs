@@ -0,0 +1,146 @@
+use crate::chunk::ChunkCore;
+use crate::common::*;
+use crate::math::Vec3;
+
+/// a 2d noise function returning values in -1..1
+pub trait Noise {
+	fn noise_2d(&self, x: f64, y: f64) -> f64;
+}
+
+pub struct TerrainGenerator<N: Noise> {
+	detail: N,
+	mountain_mask: N,
+	mountain: N,
+	mountain_detail: N,
+}
+
+impl<N: Noise> TerrainGenerator<N> {
+	pub fn loc_has_terrain(loc: ChunkLoc) -> bool {
+		loc.1 < 3 && loc.1 > -4
+	}
+
+	/// whether any of the chunks in a lod region can have terrain
+	/// a region at `level` spans 2^level chunks on each axis
+	pub fn lod_has_terrain(loc: ChunkLoc, level: u8) -> bool {
+		let size = 1 << level;
+		(loc.1 * size..(loc.1 + 1) * size).any(|y| Self::loc_has_terrain((loc.0, y, loc.2)))
+	}
+
+
+	/// the terrain height is `detail * 16 + sigmoid(mountain_mask) * (mountain * 100 + mountain_detail * 16)`
+	pub fn new(detail: N, mountain_mask: N, mountain: N, mountain_detail: N) -> Self {
+		Self {
+			detail,
+			mountain_mask,
+			mountain,
+			mountain_detail,
+		}
+	}
+
+	pub fn generate(&self, wpos: Vec3) -> ChunkCore {
+		if !Self::loc_has_terrain(wpos_to_loc(wpos)) {
+			return ChunkCore::new();
+		}
+		self.generate_scaled(wpos, 1)
+	}
+
+	/// generate a chunk where each voxel covers `scale` voxels on each axis, for lod regions
+	/// each voxel takes the type of the voxel in the middle of the area it covers
+	pub fn generate_scaled(&self, wpos: Vec3, scale: usize) -> ChunkCore {
+		let mut new_core = ChunkCore::new();
+		let scale_f = scale as f32;
+		// the middle voxel, so scale 1 gives exactly the full detail terrain
+		let middle = (scale_f - 1.0) * 0.5;
+
+		for x in 0..WIDTH {
+			for z in 0..WIDTH {
+				let world_x = (x as f32 * scale_f + middle + wpos.x) as f64;
+				let world_z = (z as f32 * scale_f + middle + wpos.z) as f64;
+				let height = self.height(world_x, world_z) as f32;
+				for y in 0..WIDTH {
+					let pos_y = y as f32 * scale_f + middle + wpos.y;
+					if  pos_y < height {
+						new_core.set_voxel(uvec3(x, y, z), 1);
+						new_core.empty = false;
+					}
+					else if pos_y < height + 2.0 {
+						new_core.set_voxel(uvec3(x, y, z), 2);
+						new_core.empty = false;
+					}
+					else if pos_y < height + 3.0 {
+						new_core.set_voxel(uvec3(x, y, z), 3);
+						new_core.empty = false;
+					}
+				}
+			}
+		}
+		new_core.compact();
+		new_core
+	}
+
+	pub fn height(&self, x: f64, y: f64) -> f64 {
+		self.detail.noise_2d(x, y) * 16.0 + 
+		sigmoid(self.mountain_mask.noise_2d(x, y), 8.0)
+			* (self.mountain.noise_2d(x, y) * 100.0
+			+ self.mountain_detail.noise_2d(x, y) * 16.0)
+	}
+}
+
+fn sigmoid(x: f64, k: f64) -> f64 {
+	1.0 / (1.0 + std::f64::consts::E.powf(-k*x))
+} 
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	struct Flat;
+
+	impl Noise for Flat {
+		fn noise_2d(&self, _x: f64, _y: f64) -> f64 {
+			0.0
+		}
+	}
+
+	fn generator() -> TerrainGenerator<Flat> {
+		TerrainGenerator::new(Flat, Flat, Flat, Flat)
+	}
+
+	#[test]
+	fn flat_terrain_layers() {
+		let core = generator().generate(Vec3::ZERO);
+		assert!(!core.empty);
+		for x in [0, 17, 31] {
+			assert_eq!(core.get_voxel(ivec3(x, 0, 5)), 2);
+			assert_eq!(core.get_voxel(ivec3(x, 1, 5)), 2);
+			assert_eq!(core.get_voxel(ivec3(x, 2, 5)), 3);
+			assert_eq!(core.get_voxel(ivec3(x, 3, 5)), EMPTY);
+		}
+		let below = generator().generate(ivec3(0, -32, 0));
+		assert_eq!(below.get_voxel(ivec3(4, 31, 4)), 1);
+		assert_eq!(below.serialize().len(), 4);
+	}
+
+	#[test]
+	fn no_terrain_far_from_the_surface() {
+		assert!(generator().generate(ivec3(0, 320, 0)).empty);
+		assert!(generator().generate(ivec3(0, -320, 0)).empty);
+		assert!(TerrainGenerator::<Flat>::lod_has_terrain((0, 0, 0), 2));
+		assert!(!TerrainGenerator::<Flat>::lod_has_terrain((0, 1, 0), 2));
+	}
+
+	#[test]
+	fn scale_one_matches_full_detail() {
+		let wpos = ivec3(32, 0, -64);
+		let full = generator().generate(wpos);
+		let scaled = generator().generate_scaled(wpos, 1);
+		assert_eq!(full.serialize(), scaled.serialize());
+
+		// each voxel of a scale 2 chunk covers 2 voxels, so the 3 voxel thick surface is 1 voxel thick
+		let coarse = generator().generate_scaled(Vec3::ZERO, 2);
+		assert_eq!(coarse.get_voxel(ivec3(0, 0, 0)), 2);
+		assert_eq!(coarse.get_voxel(ivec3(0, 1, 0)), 3);
+		assert_eq!(coarse.get_voxel(ivec3(0, 2, 0)), EMPTY);
+	}
+}
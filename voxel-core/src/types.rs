@@ -0,0 +1,135 @@
+use std::sync::OnceLock;
+
+use crate::common::*;
+use crate::shapes::{ModelQuad, Shape};
+
+
+static TYPES: OnceLock<VoxelTypes> = OnceLock::new();
+/// used until the real types are set, only knows air
+static DEFAULT_TYPES: OnceLock<VoxelTypes> = OnceLock::new();
+
+/// the voxel types every lookup through VoxelData uses
+#[inline]
+pub fn voxel_types() -> &'static VoxelTypes {
+	TYPES.get().unwrap_or_else(|| DEFAULT_TYPES.get_or_init(VoxelTypes::new))
+}
+
+/// set the voxel types once, before any chunks are generated or meshed
+/// returns the types back if they were already set
+pub fn set_voxel_types(types: VoxelTypes) -> Result<(), VoxelTypes> {
+	TYPES.set(types)
+}
+
+/// the properties of a voxel type that storage and meshing depend on
+#[derive(Clone)]
+pub struct VoxelProperties {
+	pub name: String,
+	pub transparent: bool,
+	pub solid: bool,
+	/// rays that ignore unselectable voxels go through it
+	pub selectable: bool,
+	/// texture array layer of each face, in face order
+	pub layers: [u16; 6],
+	pub shape: Shape,
+	/// geometry of every shape except cubes
+	pub model: Option<Vec<ModelQuad>>,
+}
+
+/// voxel properties indexed by id
+/// ids without properties are opaque and solid cubes
+pub struct VoxelTypes {
+	types: Vec<Option<VoxelProperties>>,
+}
+
+
+impl VoxelTypes {
+	/// a table with only air in it
+	pub fn new() -> Self {
+		let mut types = Self { types: Vec::new() };
+		types.insert(EMPTY, VoxelProperties {
+			name: "air".into(),
+			transparent: true,
+			solid: false,
+			selectable: false,
+			layers: [0; 6],
+			shape: Shape::Cube,
+			model: None,
+		});
+		types
+	}
+
+	pub fn insert(&mut self, id: Voxel, properties: VoxelProperties) {
+		let index = id as usize;
+		if self.types.len() <= index {
+			self.types.resize_with(index + 1, || None);
+		}
+		self.types[index] = Some(properties);
+	}
+
+	#[inline]
+	pub fn get(&self, id: Voxel) -> Option<&VoxelProperties> {
+		self.types.get(id as usize)?.as_ref()
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (Voxel, &VoxelProperties)> {
+		self.types.iter()
+			.enumerate()
+			.filter_map(|(id, t)| Some((id as Voxel, t.as_ref()?)))
+	}
+}
+
+impl Default for VoxelTypes {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+
+impl VoxelProperties {
+	/// an opaque, solid, selectable cube with one texture layer on every face
+	pub fn cube(name: &str, layer: u16) -> Self {
+		Self {
+			name: name.into(),
+			transparent: false,
+			solid: true,
+			selectable: true,
+			layers: [layer; 6],
+			shape: Shape::Cube,
+			model: None,
+		}
+	}
+}
+
+
+/// ids used by the tests: 1 to 3 are unregistered opaque cubes
+#[cfg(test)]
+pub(crate) mod test_types {
+	use super::*;
+
+	pub const GLASS: Voxel = 4;
+	pub const SLAB: Voxel = 5;
+	pub const PLANT: Voxel = 6;
+
+	/// the types are global, so every test that needs them sets the same ones
+	pub fn init() {
+		let mut types = VoxelTypes::new();
+		types.insert(GLASS, VoxelProperties {
+			transparent: true,
+			..VoxelProperties::cube("glass", 1)
+		});
+		types.insert(SLAB, VoxelProperties {
+			shape: Shape::Slab,
+			model: crate::shapes::build_model(Shape::Slab, 0, None),
+			..VoxelProperties::cube("slab", 2)
+		});
+		types.insert(PLANT, VoxelProperties {
+			transparent: true,
+			solid: false,
+			selectable: false,
+			shape: Shape::Cross,
+			model: crate::shapes::build_model(Shape::Cross, 0, None),
+			..VoxelProperties::cube("plant", 3)
+		});
+		set_voxel_types(types).ok();
+	}
+}